@@ -100,6 +100,69 @@ describe("RBush Comprehensive Benchmark", () => {
     })
   })
 
+  test("Benchmark: Search (100k results, preallocated)", () => {
+    console.log("\n Search 100k results ")
+    const { data: bigData, flatData: bigDataFlat } = generateData(100000)
+    const wasmTree = new RBushWasm(9)
+    wasmTree.loadHybrid(bigDataFlat, bigData)
+    const everything = { minX: -Infinity, minY: -Infinity, maxX: Infinity, maxY: Infinity }
+
+    benchmark(
+      "WASM RBush (search)",
+      () => {
+        wasmTree.search(everything)
+      },
+      10
+    )
+
+    benchmark(
+      "WASM RBush (searchCapacity)",
+      () => {
+        wasmTree.searchCapacity(everything, bigData.length)
+      },
+      10
+    )
+  })
+
+  test("Benchmark: Search (100k results, structure of arrays)", () => {
+    console.log("\n Search 100k results, columnar read ")
+    const { data: bigData, flatData: bigDataFlat } = generateData(100000)
+    const wasmTree = new RBushWasm(9)
+    wasmTree.loadHybrid(bigDataFlat, bigData)
+    const everything = { minX: -Infinity, minY: -Infinity, maxX: Infinity, maxY: Infinity }
+
+    benchmark(
+      "WASM RBush (search)",
+      () => {
+        let area = 0
+        for (const item of wasmTree.search(everything)) {
+          area += (item.maxX - item.minX) * (item.maxY - item.minY)
+        }
+        return area
+      },
+      10
+    )
+
+    benchmark(
+      "WASM RBush (searchSoa)",
+      () => {
+        const { minX, minY, maxX, maxY } = wasmTree.searchSoa(everything)
+        let area = 0
+        for (let i = 0; i < minX.length; i++) {
+          area += (maxX[i] - minX[i]) * (maxY[i] - minY[i])
+        }
+        return area
+      },
+      10
+    )
+
+    const soa = wasmTree.searchSoa(searchBox)
+    const ids = wasmTree.search(searchBox).map((item) => item.id).sort((a, b) => a - b)
+    expect(soa.data.map((item) => item.id).sort((a, b) => a - b)).toEqual(ids)
+    expect(soa.minX.length).toBe(ids.length)
+    soa.data.forEach((item, i) => expect(soa.minX[i]).toBe(item.minX))
+  })
+
   test("Benchmark: Collides", () => {
     console.log("\n Collides ")
     const jsTree = new RBushJS(9)
@@ -166,3 +229,3351 @@ describe("RBush Comprehensive Benchmark", () => {
     )
   })
 })
+
+describe("RBush Robustness", () => {
+  function bruteSearch(items, bbox) {
+    return items.filter(
+      (b) => b.minX <= bbox.maxX && b.minY <= bbox.maxY && b.maxX >= bbox.minX && b.maxY >= bbox.minY
+    )
+  }
+
+  function sortedIds(items) {
+    return items.map((item) => item.id).sort((a, b) => a - b)
+  }
+
+  test("handles coordinates spanning -1e15 to 1e15", () => {
+    const items = []
+    for (let i = 0; i < 2000; i++) {
+      const minX = (Math.random() * 2 - 1) * 1e15
+      const minY = (Math.random() * 2 - 1) * 1e15
+      items.push({ minX, minY, maxX: minX + Math.random() * 1e12, maxY: minY + Math.random() * 1e12, id: i })
+    }
+
+    const tree = new RBushWasm(9)
+    for (const item of items) tree.insert(item)
+
+    const bbox = { minX: -2e14, minY: -3e14, maxX: 4e14, maxY: 1e14 }
+    expect(sortedIds(tree.search(bbox))).toEqual(sortedIds(bruteSearch(items, bbox)))
+  })
+
+  test("still inserts and searches when areas overflow to infinity", () => {
+    const items = []
+    for (let i = 0; i < 200; i++) {
+      const minX = (Math.random() * 2 - 1) * 1e300
+      const minY = (Math.random() * 2 - 1) * 1e300
+      items.push({ minX, minY, maxX: minX + 1e299, maxY: minY + 1e299, id: i })
+    }
+
+    const tree = new RBushWasm(4)
+    for (const item of items) tree.insert(item)
+
+    const bbox = { minX: -1e300, minY: -1e300, maxX: 1e300, maxY: 1e300 }
+    expect(sortedIds(tree.search(bbox))).toEqual(sortedIds(bruteSearch(items, bbox)))
+    expect(tree.all().length).toBe(items.length)
+  })
+})
+
+describe("RBush Clone Structure", () => {
+  test("queries on the copy return null in place of each item", () => {
+    const tree = new RBushWasm(4)
+    const items = []
+    for (let i = 0; i < 100; i++) {
+      items.push({ minX: i % 10, minY: Math.floor(i / 10), maxX: i % 10, maxY: Math.floor(i / 10), id: i })
+    }
+    tree.load(items)
+    const copy = tree.cloneStructure()
+
+    const box = { minX: 2, minY: 2, maxX: 4, maxY: 5 }
+    const found = copy.search(box)
+    expect(found.length).toBe(tree.search(box).length)
+    expect(found.every((item) => item === null)).toBe(true)
+    expect(copy.all()).toEqual(new Array(100).fill(null))
+    expect(copy.knn(0, 0, 3)).toEqual([null, null, null])
+    expect(copy.collides(box)).toBe(true)
+    expect(copy.toJSON()).toEqual(JSON.parse(JSON.stringify(tree.toJSON(), (key, value) => (value && value.id !== undefined ? null : value))))
+
+    // the original keeps its items
+    expect(tree.search(box).every((item) => items.includes(item))).toBe(true)
+  })
+})
+
+describe("RBush Search Adaptive", () => {
+  const tree = new RBushWasm(4)
+  const items = []
+  for (let i = 0; i < 900; i++) {
+    items.push({ minX: i % 30, minY: Math.floor(i / 30), maxX: i % 30, maxY: Math.floor(i / 30), id: i })
+  }
+  tree.load(items)
+  const isSummary = (entry) => entry.count !== undefined
+
+  test("summarizes dense subtrees inside the box and returns sparse items", () => {
+    const everything = { minX: -1, minY: -1, maxX: 30, maxY: 30 }
+    const result = tree.searchAdaptive(everything, 1000)
+    expect(result.length).toBe(900)
+    expect(result.some(isSummary)).toBe(false)
+
+    const coarse = tree.searchAdaptive(everything, 10)
+    expect(coarse.length).toBe(1)
+    expect(coarse[0]).toEqual({ minX: 0, minY: 0, maxX: 29, maxY: 29, count: 900 })
+  })
+
+  test("a box straddling dense nodes summarizes only what lies inside it", () => {
+    // cuts through the root and through nodes at every level below it
+    const box = { minX: 3.5, minY: 2.5, maxX: 21.5, maxY: 17.5 }
+    const result = tree.searchAdaptive(box, 8)
+    const summaries = result.filter(isSummary)
+    const found = result.filter((entry) => !isSummary(entry))
+
+    expect(summaries.length).toBeGreaterThan(0)
+    expect(found.length).toBeGreaterThan(0)
+    for (const summary of summaries) {
+      expect(summary.count).toBeGreaterThan(8)
+      expect(summary.minX).toBeGreaterThanOrEqual(box.minX)
+      expect(summary.minY).toBeGreaterThanOrEqual(box.minY)
+      expect(summary.maxX).toBeLessThanOrEqual(box.maxX)
+      expect(summary.maxY).toBeLessThanOrEqual(box.maxY)
+    }
+
+    const expected = tree.search(box)
+    const summarized = summaries.reduce((sum, summary) => sum + summary.count, 0)
+    expect(summarized + found.length).toBe(expected.length)
+    for (const item of found) expect(expected).toContain(item)
+  })
+})
+
+describe("RBush Max Overlap", () => {
+  test("returns the item overlapping the box the most", () => {
+    const tree = new RBushWasm(4)
+    const items = []
+    for (let i = 0; i < 50; i++) {
+      items.push({ minX: i * 10, minY: 0, maxX: i * 10 + 4, maxY: 4, id: i })
+    }
+    const big = { minX: 101, minY: 1, maxX: 109, maxY: 9, id: "big" }
+    tree.load([...items, big])
+
+    // overlaps ids 10 and 11 by 4 * 4 = 16 each and "big" by 8 * 3 = 24
+    expect(tree.maxOverlap({ minX: 100, minY: 0, maxX: 114, maxY: 4 })).toBe(big)
+    expect(tree.maxOverlap({ minX: 100, minY: 0, maxX: 101, maxY: 4 }).id).toBe(10)
+    expect(tree.maxOverlap({ minX: 1000, minY: 0, maxX: 1100, maxY: 4 })).toBe(null)
+  })
+
+  test("a tie returns one of the tied items, the same one every time", () => {
+    const tree = new RBushWasm(4)
+    const tied = []
+    for (let i = 0; i < 30; i++) {
+      const item = { minX: i * 10, minY: 0, maxX: i * 10 + 2, maxY: 2, id: i }
+      tree.insert(item)
+      tied.push(item)
+    }
+    const everything = { minX: -1, minY: -1, maxX: 1000, maxY: 3 }
+    const first = tree.maxOverlap(everything)
+    expect(tied).toContain(first)
+    expect(tree.maxOverlap(everything)).toBe(first)
+
+    // a touching item overlaps by 0 and is still returned when it's the only one
+    expect(tree.maxOverlap({ minX: 2, minY: 2, maxX: 5, maxY: 5 }).id).toBe(0)
+  })
+})
+
+describe("RBush Search Bounds", () => {
+  test("returns the union of the matching items' boxes", () => {
+    const tree = new RBushWasm(4)
+    const items = []
+    for (let i = 0; i < 300; i++) {
+      const x = Math.random() * 100
+      const y = Math.random() * 100
+      items.push({ minX: x, minY: y, maxX: x + Math.random() * 5, maxY: y + Math.random() * 5 })
+    }
+    tree.load(items)
+
+    const bbox = { minX: 20, minY: 30, maxX: 60, maxY: 50 }
+    const matches = items.filter(
+      (item) => item.minX <= bbox.maxX && item.maxX >= bbox.minX && item.minY <= bbox.maxY && item.maxY >= bbox.minY
+    )
+    expect(tree.searchBounds(bbox)).toEqual({
+      minX: Math.min(...matches.map((item) => item.minX)),
+      minY: Math.min(...matches.map((item) => item.minY)),
+      maxX: Math.max(...matches.map((item) => item.maxX)),
+      maxY: Math.max(...matches.map((item) => item.maxY)),
+    })
+    expect(tree.searchBounds({ minX: 200, minY: 200, maxX: 300, maxY: 300 })).toBe(null)
+  })
+
+  test("a node inside the box is still bounded by its items' true extents", () => {
+    const tree = new RBushWasm(4)
+    // every node holding these is grown by the padding, yet lies well
+    // inside the query box
+    for (let i = 0; i < 20; i++) {
+      tree.insertPadded({ minX: 40 + i, minY: 40, maxX: 41 + i, maxY: 41 }, 10)
+    }
+    expect(tree.searchBounds({ minX: 0, minY: 0, maxX: 100, maxY: 100 })).toEqual({
+      minX: 40,
+      minY: 40,
+      maxX: 60,
+      maxY: 41,
+    })
+  })
+})
+
+describe("RBush Search Sweep", () => {
+  const tree = new RBushWasm(4)
+  const items = []
+  for (let i = 0; i < 100; i++) {
+    // minX descends with the id, so insertion order differs from sweep order
+    items.push({ minX: 100 - i, minY: i % 10, maxX: 101 - i, maxY: (i % 10) + 1, id: i })
+  }
+  tree.load(items)
+
+  test("returns the k matches with the smallest minX, in minX order", () => {
+    const bbox = { minX: 0, minY: 0, maxX: 200, maxY: 3 }
+    const expected = items
+      .filter((item) => item.minY <= 3)
+      .sort((a, b) => a.minX - b.minX)
+      .slice(0, 5)
+    expect(tree.searchSweep(bbox, 5)).toEqual(expected)
+  })
+
+  test("fewer than k matches returns all of them, sorted by minX", () => {
+    const bbox = { minX: 10, minY: 0, maxX: 20, maxY: 20 }
+    const matches = items.filter((item) => item.minX <= 20 && item.maxX >= 10)
+    const result = tree.searchSweep(bbox, 1000)
+    expect(result.length).toBe(matches.length)
+    expect(result.map((item) => item.minX)).toEqual(matches.map((item) => item.minX).sort((a, b) => a - b))
+    expect(tree.searchSweep(bbox, 0)).toEqual([])
+    expect(tree.searchSweep({ minX: 500, minY: 500, maxX: 600, maxY: 600 }, 3)).toEqual([])
+  })
+})
+
+describe("RBush Search With Knn", () => {
+  test("pairs every match with its k nearest other matches", () => {
+    const tree = new RBushWasm(4)
+    const items = []
+    for (let i = 0; i < 60; i++) {
+      items.push({ minX: i, minY: 0, maxX: i, maxY: 0, id: i })
+    }
+    tree.load(items)
+
+    // ids 10..19 match; neighbors come only from other matches
+    const bbox = { minX: 10, minY: -1, maxX: 19, maxY: 1 }
+    const result = tree.searchWithKnn(bbox, 3)
+    expect(result.map((entry) => entry.data.id).sort((a, b) => a - b)).toEqual([10, 11, 12, 13, 14, 15, 16, 17, 18, 19])
+    const byId = new Map(result.map((entry) => [entry.data.id, entry.neighbors.map((item) => item.id)]))
+    expect(byId.get(10)).toEqual([11, 12, 13])
+    expect(byId.get(19)).toEqual([18, 17, 16])
+    expect(byId.get(14).slice(0, 2).sort()).toEqual([13, 15])
+    expect(byId.get(14)).not.toContain(14)
+  })
+
+  test("k caps the neighbors, and fewer matches give fewer neighbors", () => {
+    const tree = new RBushWasm(4)
+    tree.load([
+      { minX: 0, minY: 0, maxX: 0, maxY: 0, id: "a" },
+      { minX: 1, minY: 0, maxX: 1, maxY: 0, id: "b" },
+      { minX: 50, minY: 50, maxX: 50, maxY: 50, id: "far" },
+    ])
+    const bbox = { minX: -1, minY: -1, maxX: 2, maxY: 2 }
+    for (const entry of tree.searchWithKnn(bbox, 5)) {
+      expect(entry.neighbors.length).toBe(1)
+    }
+    for (const entry of tree.searchWithKnn(bbox, 0)) {
+      expect(entry.neighbors).toEqual([])
+    }
+    expect(tree.searchWithKnn({ minX: 10, minY: 10, maxX: 20, maxY: 20 }, 3)).toEqual([])
+  })
+})
+
+describe("RBush Timestamps", () => {
+  test("searchSince returns only items at or after the cutoff", () => {
+    const tree = new RBushWasm(4)
+    const stale = []
+    const fresh = []
+    for (let i = 0; i < 500; i++) {
+      const item = { minX: i, minY: i, maxX: i + 1, maxY: i + 1, id: i }
+      if (i < 400) {
+        // the oldest items are grouped into their own subtrees, which the
+        // per-node max timestamp prunes without visiting their leaves
+        tree.insertAtTime(item, 0)
+        stale.push(item)
+      } else {
+        tree.insertAtTime(item, 100)
+        fresh.push(item)
+      }
+    }
+    tree.insert({ minX: 450, minY: 450, maxX: 451, maxY: 451, id: "untimed" })
+
+    const bbox = { minX: 0, minY: 0, maxX: 1000, maxY: 1000 }
+    const ids = tree.searchSince(bbox, 50).map((item) => item.id)
+    expect(ids.sort((a, b) => a - b)).toEqual(fresh.map((item) => item.id))
+    expect(tree.searchSince(bbox, 0).length).toBe(stale.length + fresh.length)
+    expect(tree.searchSince(bbox, 101)).toEqual([])
+  })
+
+  test("the cutoff is inclusive", () => {
+    const tree = new RBushWasm(4)
+    const items = [9.5, 10, 10, 10.5].map((time, i) => ({ minX: i, minY: 0, maxX: i, maxY: 0, time }))
+    for (const item of items) tree.insertAtTime(item, item.time)
+
+    const bbox = { minX: 0, minY: 0, maxX: 10, maxY: 0 }
+    const times = (list) => list.map((item) => item.time).sort((a, b) => a - b)
+    expect(times(tree.searchSince(bbox, 10))).toEqual([10, 10, 10.5])
+    expect(times(tree.searchSince(bbox, 10.5))).toEqual([10.5])
+    expect(tree.searchSince(bbox, 10.500001)).toEqual([])
+  })
+})
+
+describe("RBush Transforms", () => {
+  const items = []
+  for (let i = 0; i < 300; i++) {
+    const minX = Math.random() * 1000
+    const minY = Math.random() * 1000
+    items.push({ minX, minY, maxX: minX + Math.random() * 20, maxY: minY + Math.random() * 20, id: i })
+  }
+
+  function sortedIds(results) {
+    return results.map((item) => item.id).sort((a, b) => a - b)
+  }
+
+  test("translate shifts search results", () => {
+    const tree = new RBushWasm(9)
+    tree.load(items)
+    const bbox = { minX: 200, minY: 300, maxX: 500, maxY: 700 }
+    const before = sortedIds(tree.search(bbox))
+
+    tree.translate(1000, -50)
+    const moved = { minX: 1200, minY: 250, maxX: 1500, maxY: 650 }
+    expect(sortedIds(tree.search(moved))).toEqual(before)
+    expect(tree.collides({ minX: 0, minY: 0, maxX: 999, maxY: 2000 })).toBe(false)
+  })
+
+  test("scale and transform map search results", () => {
+    const tree = new RBushWasm(9)
+    tree.load(items)
+    const bbox = { minX: 200, minY: 300, maxX: 500, maxY: 700 }
+    const before = sortedIds(tree.search(bbox))
+
+    tree.scale(2, 100, 100)
+    expect(sortedIds(tree.search({ minX: 300, minY: 500, maxX: 900, maxY: 1300 }))).toEqual(before)
+
+    tree.transform(0.5, -1, -50, 0)
+    expect(sortedIds(tree.search({ minX: 100, minY: -1300, maxX: 400, maxY: -500 }))).toEqual(before)
+  })
+})
+
+describe("RBush Auto Compaction", () => {
+  test("compacts once the removal threshold is reached", () => {
+    const items = []
+    for (let i = 0; i < 500; i++) {
+      items.push({ minX: i, minY: i % 50, maxX: i + 2, maxY: (i % 50) + 2, id: i })
+    }
+
+    const tree = new RBushWasm(9, 10)
+    tree.load(items)
+    for (let i = 0; i < 9; i++) {
+      tree.remove(items[i])
+      expect(tree.pendingRemovals()).toBe(i + 1)
+    }
+    tree.remove(items[9])
+    expect(tree.pendingRemovals()).toBe(0)
+
+    const remaining = tree.all().map((item) => item.id).sort((a, b) => a - b)
+    expect(remaining).toEqual(items.slice(10).map((item) => item.id))
+  })
+
+  test("never compacts when disabled", () => {
+    const tree = new RBushWasm(9)
+    const items = []
+    for (let i = 0; i < 100; i++) items.push({ minX: i, minY: i, maxX: i + 1, maxY: i + 1 })
+    tree.load(items)
+    for (const item of items.slice(0, 50)) tree.remove(item)
+    expect(tree.pendingRemovals()).toBe(50)
+  })
+})
+
+describe("RBush Tags", () => {
+  test("searchTags filters by mask and skips subtrees with disjoint masks", () => {
+    const tree = new RBushWasm(4)
+    const roads = []
+    const buildings = []
+    for (let i = 0; i < 300; i++) {
+      // roads and buildings live in separate regions, so whole subtrees carry
+      // only one of the two tags and are pruned by the other mask
+      const road = { minX: i, minY: 0, maxX: i + 1, maxY: 1, id: i }
+      const building = { minX: i, minY: 500, maxX: i + 1, maxY: 501, id: 1000 + i }
+      tree.insertTaggedBits(road, 0b01)
+      tree.insertTaggedBits(building, 0b10)
+      roads.push(road)
+      buildings.push(building)
+    }
+
+    const everywhere = { minX: -1, minY: -1, maxX: 1000, maxY: 1000 }
+    const ids = (results) => results.map((item) => item.id).sort((a, b) => a - b)
+    expect(ids(tree.searchTags(everywhere, 0b01))).toEqual(ids(roads))
+    expect(ids(tree.searchTags(everywhere, 0b10))).toEqual(ids(buildings))
+    expect(tree.searchTags(everywhere, 0b11).length).toBe(600)
+    expect(tree.searchTags(everywhere, 0b100)).toEqual([])
+  })
+})
+
+describe("RBush Reinsert Worst", () => {
+  test("Benchmark: search before/after reinserting the worst 1%", () => {
+    // one-by-one inserts of wide, strongly overlapping boxes degrade the tree
+    const items = []
+    for (let i = 0; i < 20000; i++) {
+      const x = Math.random() * 1000
+      const y = Math.random() * 1000
+      items.push({ minX: x, minY: y, maxX: x + Math.random() * 80, maxY: y + Math.random() * 80, id: i })
+    }
+    const tree = new RBushWasm(9)
+    for (const item of items) tree.insert(item)
+
+    const boxes = []
+    for (let i = 0; i < 200; i++) {
+      const x = Math.random() * 950
+      const y = Math.random() * 950
+      boxes.push({ minX: x, minY: y, maxX: x + 50, maxY: y + 50 })
+    }
+    const run = () => {
+      const start = performance.now()
+      let found = 0
+      for (const box of boxes) found += tree.search(box).length
+      return { ms: performance.now() - start, found }
+    }
+
+    const before = run()
+    const reinserted = tree.reinsertWorst(items.length / 100)
+    const after = run()
+    console.log(`reinsertWorst(${reinserted}): ${before.ms.toFixed(3)} ms -> ${after.ms.toFixed(3)} ms`)
+
+    expect(reinserted).toBe(items.length / 100)
+    expect(after.found).toBe(before.found)
+    expect(tree.all().length).toBe(items.length)
+  })
+})
+
+describe("RBush JSON Interop", () => {
+  const items = []
+  for (let i = 0; i < 1000; i++) {
+    const x = Math.random() * 1000
+    const y = Math.random() * 1000
+    items.push({ minX: x, minY: y, maxX: x + Math.random() * 20, maxY: y + Math.random() * 20, id: i })
+  }
+  const boxes = [
+    { minX: 0, minY: 0, maxX: 1000, maxY: 1000 },
+    { minX: 200, minY: 300, maxX: 450, maxY: 500 },
+    { minX: 900, minY: 900, maxX: 905, maxY: 905 },
+  ]
+  const sortedIds = (results) => results.map((item) => item.id).sort((a, b) => a - b)
+
+  test("toJSON loads into JS rbush with identical searches", () => {
+    const wasmTree = new RBushWasm(9)
+    wasmTree.load(items)
+    const jsTree = new RBushJS(9).fromJSON(wasmTree.toJSON())
+
+    for (const box of boxes) {
+      expect(sortedIds(jsTree.search(box))).toEqual(sortedIds(wasmTree.search(box)))
+    }
+    expect(JSON.stringify(jsTree.toJSON())).toBe(JSON.stringify(wasmTree.toJSON()))
+  })
+
+  test("JS rbush toJSON round-trips through fromJSON unchanged", () => {
+    const jsTree = new RBushJS(9)
+    jsTree.load(items)
+    const wasmTree = new RBushWasm(9)
+    wasmTree.fromJSON(jsTree.toJSON())
+
+    expect(JSON.stringify(wasmTree.toJSON())).toBe(JSON.stringify(jsTree.toJSON()))
+    for (const box of boxes) {
+      expect(sortedIds(wasmTree.search(box))).toEqual(sortedIds(jsTree.search(box)))
+    }
+  })
+
+  test("toJSON round-trips through fromJSON with identical searches", () => {
+    const tree = new RBushWasm(9)
+    tree.load(items)
+    const restored = new RBushWasm(9)
+    restored.fromJSON(tree.toJSON())
+
+    expect(restored.structurallyEquals(tree)).toBe(true)
+    for (const box of boxes) {
+      expect(restored.search(box)).toEqual(tree.search(box))
+    }
+  })
+
+  test("fromJSON rejects inconsistent child heights and keeps the tree", () => {
+    const tree = new RBushWasm(9)
+    tree.load(items)
+    const before = tree.toJSON()
+
+    const json = new RBushWasm(4)
+    json.load(items)
+    const malformed = json.toJSON()
+    // graft a leaf node one level too high, next to regular subtrees
+    malformed.children.push(malformed.children[0].children[0])
+    expect(malformed.height).toBeGreaterThan(2)
+    expect(() => tree.fromJSON(malformed)).toThrow("inconsistent node heights")
+
+    const wrongHeight = json.toJSON()
+    wrongHeight.children[0].height += 1
+    expect(() => tree.fromJSON(wrongHeight)).toThrow("inconsistent node heights")
+
+    expect(tree.toJSON()).toEqual(before)
+    for (const box of boxes) {
+      expect(sortedIds(tree.search(box))).toEqual(sortedIds(json.search(box)))
+    }
+  })
+
+  test("empty tree matches JS rbush", () => {
+    expect(JSON.stringify(new RBushWasm(9).toJSON())).toBe(JSON.stringify(new RBushJS(9).toJSON()))
+  })
+})
+
+describe("RBush Transfer Region", () => {
+  test("transferRegion preserves the union of both trees' searches", () => {
+    const items = []
+    for (let i = 0; i < 2000; i++) {
+      const x = Math.random() * 1000
+      const y = Math.random() * 1000
+      items.push({ minX: x, minY: y, maxX: x + Math.random() * 10, maxY: y + Math.random() * 10, id: i })
+    }
+    const pending = new RBushWasm(9)
+    pending.load(items)
+    const committed = new RBushWasm(9)
+    committed.load([{ minX: 5000, minY: 5000, maxX: 5001, maxY: 5001, id: -1 }])
+
+    const region = { minX: 100, minY: 100, maxX: 600, maxY: 600 }
+    const expected = items.filter(
+      (item) => item.minX >= 100 && item.minY >= 100 && item.maxX <= 600 && item.maxY <= 600
+    )
+    expect(pending.transferRegion(committed, region)).toBe(expected.length)
+
+    const ids = (results) => results.map((item) => item.id).sort((a, b) => a - b)
+    const everywhere = { minX: -1, minY: -1, maxX: 10000, maxY: 10000 }
+    const union = ids([...pending.search(everywhere), ...committed.search(everywhere)])
+    expect(union).toEqual([-1, ...items.map((item) => item.id)])
+
+    expect(ids(committed.search(region))).toEqual(ids(expected))
+    expect(pending.search(region).every((item) => !expected.includes(item))).toBe(true)
+  })
+})
+
+describe("RBush Distance Metrics", () => {
+  // from the origin: east is nearest by Euclidean distance (4.12), north by
+  // Manhattan (4.5), diagonal by Chebyshev (3)
+  const origin = { minX: 0, minY: 0, maxX: 0, maxY: 0, id: "origin" }
+  const items = [
+    origin,
+    { minX: 3, minY: 3, maxX: 3, maxY: 3, id: "diagonal" },
+    { minX: 5, minY: 0, maxX: 5, maxY: 0, id: "far" },
+    { minX: 0, minY: 4.5, maxX: 0, maxY: 4.5, id: "north" },
+    { minX: 4, minY: 1, maxX: 4, maxY: 1, id: "east" },
+  ]
+  const everything = { minX: -10, minY: -10, maxX: 10, maxY: 10 }
+  const nearestToOrigin = (tree, k) =>
+    tree.searchWithKnn(everything, k).find((entry) => entry.data === origin).neighbors.map((item) => item.id)
+
+  test.each([
+    ["euclidean", ["east", "diagonal", "north"]],
+    ["manhattan", ["north", "far", "east"]],
+    ["chebyshev", ["diagonal", "east", "north"]],
+  ])("%s ordering", (metric, expected) => {
+    const tree = new RBushWasm(4)
+    tree.load(items)
+    tree.setMetric(metric)
+    expect(tree.metric()).toBe(metric)
+    const nearest = nearestToOrigin(tree, 3)
+    expect(nearest[0]).toBe(expected[0])
+    if (metric !== "manhattan") expect(nearest).toEqual(expected)
+    else expect(nearest.slice(1).sort()).toEqual(["east", "far"])
+  })
+
+  test("rejects unknown metrics", () => {
+    expect(() => new RBushWasm().setMetric("cosine")).toThrow()
+  })
+})
+
+describe("RBush Estimate Count", () => {
+  test("estimateCount tracks the exact count on uniform data", () => {
+    const items = []
+    for (let i = 0; i < 50000; i++) {
+      const x = Math.random() * 1000
+      const y = Math.random() * 1000
+      items.push({ minX: x, minY: y, maxX: x + 1, maxY: y + 1 })
+    }
+    const tree = new RBushWasm(9)
+    tree.load(items)
+
+    for (const size of [100, 300, 600]) {
+      const box = { minX: 200, minY: 150, maxX: 200 + size, maxY: 150 + size }
+      const exact = tree.search(box).length
+      const estimate = tree.estimateCount(box)
+      console.log(`estimateCount ${size}x${size}: exact ${exact}, estimate ${estimate.toFixed(1)}`)
+      expect(Math.abs(estimate - exact) / exact).toBeLessThan(0.15)
+    }
+    expect(tree.estimateCount({ minX: 2000, minY: 2000, maxX: 3000, maxY: 3000 })).toBe(0)
+  })
+})
+
+describe("RBush Streaming Load", () => {
+  test("chunks stay invisible until finishLoad builds them", () => {
+    const tree = new RBushWasm(9)
+    const everywhere = { minX: -1, minY: -1, maxX: 2000, maxY: 2000 }
+    tree.beginLoad()
+
+    const items = []
+    for (let chunk = 0; chunk < 4; chunk++) {
+      const coords = new Float64Array(250 * 4)
+      const chunkItems = []
+      for (let i = 0; i < 250; i++) {
+        const x = Math.random() * 1000
+        const y = Math.random() * 1000
+        coords.set([x, y, x + 5, y + 5], i * 4)
+        chunkItems.push({ id: chunk * 250 + i })
+      }
+      tree.pushLoadChunk(coords, chunkItems)
+      items.push(...chunkItems)
+    }
+    expect(tree.search(everywhere)).toEqual([])
+
+    tree.finishLoad()
+    const ids = tree.search(everywhere).map((item) => item.id).sort((a, b) => a - b)
+    expect(ids).toEqual(items.map((item) => item.id))
+  })
+})
+
+describe("RBush Radius With Distance", () => {
+  test("returns items within the radius with their distances", () => {
+    const tree = new RBushWasm(4)
+    tree.load([
+      { minX: 0, minY: 0, maxX: 2, maxY: 2, id: "inside" },
+      { minX: 5, minY: 0, maxX: 6, maxY: 1, id: "east" },
+      { minX: 3, minY: 4, maxX: 3, maxY: 4, id: "diagonal" },
+      { minX: 20, minY: 20, maxX: 21, maxY: 21, id: "far" },
+    ])
+
+    const results = tree.searchRadiusWithDistance(1, 1, 4, true)
+    expect(results.map((r) => r.data.id)).toEqual(["inside", "diagonal", "east"])
+    expect(results.map((r) => r.distance)).toEqual([0, Math.hypot(2, 3), 4])
+
+    tree.setMetric("chebyshev")
+    expect(tree.searchRadiusWithDistance(1, 1, 3, true).map((r) => r.data.id)).toEqual(["inside", "diagonal"])
+  })
+})
+
+describe("RBush KNN", () => {
+  test("knn matches a brute-force sort by point-to-bbox distance", () => {
+    const items = []
+    for (let i = 0; i < 3000; i++) {
+      const x = Math.random() * 1000
+      const y = Math.random() * 1000
+      items.push({ minX: x, minY: y, maxX: x + Math.random() * 10, maxY: y + Math.random() * 10, id: i })
+    }
+    const tree = new RBushWasm(9)
+    tree.load(items)
+
+    const dist = (item, x, y) =>
+      Math.hypot(Math.max(0, item.minX - x, x - item.maxX), Math.max(0, item.minY - y, y - item.maxY))
+    const [x, y] = [412, 587]
+    const expected = items.map((item) => dist(item, x, y)).sort((a, b) => a - b).slice(0, 10)
+    const result = tree.knn(x, y, 10)
+    expect(result.map((item) => dist(item, x, y))).toEqual(expected)
+    expect(tree.knn(x, y, 0)).toEqual([])
+    expect(new RBushWasm().knn(x, y, 5)).toEqual([])
+  })
+})
+
+describe("RBush KNN Within", () => {
+  test("knnWithin stops at the distance cutoff, inclusive", () => {
+    const tree = new RBushWasm(4)
+    tree.load([
+      { minX: 1, minY: 0, maxX: 1, maxY: 0, id: "a" },
+      { minX: 0, minY: 3, maxX: 0, maxY: 3, id: "b" },
+      { minX: 5, minY: 0, maxX: 5, maxY: 0, id: "c" },
+      { minX: 9, minY: 9, maxX: 9, maxY: 9, id: "d" },
+    ])
+
+    expect(tree.knnWithin(0, 0, 10, 3).map((item) => item.id)).toEqual(["a", "b"])
+    expect(tree.knnWithin(0, 0, 1, 3).map((item) => item.id)).toEqual(["a"])
+    expect(tree.knnWithin(0, 0, 10, 0.5)).toEqual([])
+  })
+})
+
+describe("RBush Padded Inserts", () => {
+  test("small moves stay inside the padding and skip reinserts", () => {
+    const tree = new RBushWasm(9)
+    const items = []
+    for (let i = 0; i < 500; i++) {
+      const x = Math.random() * 1000
+      const y = Math.random() * 1000
+      const item = { minX: x, minY: y, maxX: x + 2, maxY: y + 2, id: i }
+      items.push(item)
+      tree.insertPadded(item, 5)
+    }
+
+    const move = (item, dx, dy) => {
+      item.minX += dx
+      item.maxX += dx
+      item.minY += dy
+      item.maxY += dy
+    }
+    for (const item of items) {
+      move(item, (Math.random() - 0.5) * 4, (Math.random() - 0.5) * 4)
+      expect(tree.updatePadded(item)).toBe(true)
+    }
+    expect(tree.opStats().inserts).toBe(items.length)
+
+    // a move past the padding needs a remove and reinsert
+    const far = items[1]
+    move(far, 50, 0)
+    expect(tree.updatePadded(far)).toBe(false)
+
+    // searches use the true extent, not the padded box
+    const item = items[0]
+    const gap = { minX: item.maxX + 1, minY: item.minY, maxX: item.maxX + 3, maxY: item.maxY }
+    expect(tree.search(gap)).not.toContain(item)
+    expect(tree.search(item)).toContain(item)
+  })
+
+  test("every item-level query ignores the padding", () => {
+    const tree = new RBushWasm(4)
+    const padded = { minX: 0, minY: 0, maxX: 2, maxY: 2, id: "padded" }
+    tree.insertPadded(padded, 5)
+
+    // inside the padding, 1 to 3 units right of the true extent
+    const edge = { minX: 3, minY: 0, maxX: 5, maxY: 2 }
+    expect(tree.search(edge)).toEqual([])
+    expect(tree.searchBfs(edge)).toEqual([])
+    expect(tree.searchAdaptive(edge, 0)).toEqual([])
+    expect(tree.searchBudgeted(edge, 10).results).toEqual([])
+    expect(tree.searchByLeaf(edge)).toEqual([])
+    expect(tree.searchPartition(edge).inside).toEqual([])
+    expect(tree.searchVLine(4)).toEqual([])
+    expect(tree.searchHLine(-2)).toEqual([])
+    expect(tree.collides(edge)).toBe(false)
+    expect(tree.collidesSampled(edge, 10)).toBe(false)
+    expect(tree.maxOverlap(edge)).toBe(null)
+    expect(tree.coversPoint(4, 1)).toBe(false)
+    expect(tree.coverageCountAt(4, 1)).toBe(0)
+    expect(tree.searchBounds(edge)).toBe(null)
+
+    // outputs report the true extent
+    const box = { minX: 1, minY: 1, maxX: 3, maxY: 3 }
+    expect(tree.searchSoa(box).minX).toEqual(new Float64Array([0]))
+    expect(tree.searchBounds(box)).toEqual({ minX: 0, minY: 0, maxX: 2, maxY: 2 })
+    expect(tree.maxOverlap(box)).toBe(padded)
+    expect(tree.coverageCountAt(1, 1)).toBe(1)
+
+    // distances are measured to the true extent
+    const near = { minX: 5, minY: 0, maxX: 6, maxY: 1, id: "near" }
+    tree.insert(near)
+    expect(tree.knn(4, 1, 1)).toEqual([near])
+    const within = tree.searchRadiusWithDistance(4, 1, 1.5)
+    expect(within.map((hit) => hit.data)).toEqual([near])
+    expect(within[0].distance).toBe(1)
+  })
+})
+
+describe("RBush Size", () => {
+  test("len tracks inserts, loads, removals and clear", () => {
+    const tree = new RBushWasm(4)
+    expect(tree.len()).toBe(0)
+    expect(tree.isEmpty()).toBe(true)
+
+    const items = []
+    for (let i = 0; i < 100; i++) items.push({ minX: i, minY: i, maxX: i + 1, maxY: i + 1 })
+    tree.load(items.slice(0, 60))
+    for (const item of items.slice(60)) tree.insert(item)
+    expect(tree.len()).toBe(100)
+
+    for (const item of items.slice(0, 30)) tree.remove(item)
+    expect(tree.len()).toBe(70)
+    expect(tree.isEmpty()).toBe(false)
+
+    tree.clear()
+    expect(tree.len()).toBe(0)
+  })
+})
+
+describe("RBush Top Overlaps", () => {
+  test("topOverlaps ranks intersecting items by overlap area", () => {
+    const tree = new RBushWasm(4)
+    tree.load([
+      { minX: 0, minY: 0, maxX: 10, maxY: 10, id: "full" },
+      { minX: 5, minY: 5, maxX: 15, maxY: 15, id: "quarter" },
+      { minX: 8, minY: 0, maxX: 20, maxY: 10, id: "strip" },
+      { minX: 10, minY: 10, maxX: 12, maxY: 12, id: "corner" },
+      { minX: 30, minY: 30, maxX: 40, maxY: 40, id: "outside" },
+    ])
+    const box = { minX: 0, minY: 0, maxX: 10, maxY: 10 }
+
+    expect(tree.topOverlaps(box, 2).map((item) => item.id)).toEqual(["full", "quarter"])
+    expect(tree.topOverlaps(box, 10).map((item) => item.id)).toEqual(["full", "quarter", "strip", "corner"])
+    expect(tree.topOverlaps(box, 0)).toEqual([])
+  })
+})
+
+describe("RBush Frozen Regions", () => {
+  test("frozen nodes reject inserts and removals but allow changes elsewhere", () => {
+    const base = []
+    for (let i = 0; i < 200; i++) {
+      const x = Math.random() * 100
+      const y = Math.random() * 100
+      base.push({ minX: x, minY: y, maxX: x + 1, maxY: y + 1, id: i })
+    }
+    const tree = new RBushWasm(9)
+    tree.load(base)
+    expect(tree.freezeRegion({ minX: -1, minY: -1, maxX: 102, maxY: 102 })).toBeGreaterThan(0)
+
+    expect(() => tree.insert({ minX: 50, minY: 50, maxX: 51, maxY: 51 })).toThrow()
+    expect(() => tree.remove(base[0])).toThrow()
+    expect(() => tree.optimize()).toThrow()
+    expect(tree.len()).toBe(200)
+
+    tree.unfreezeAll()
+    tree.remove(base[0])
+    expect(tree.len()).toBe(199)
+  })
+
+  test("items outside a frozen subtree can still be inserted and removed", () => {
+    const tree = new RBushWasm(4)
+    const items = []
+    for (let i = 0; i < 400; i++) {
+      const x = (i % 20) * 10
+      const y = Math.floor(i / 20) * 10
+      items.push({ minX: x, minY: y, maxX: x + 1, maxY: y + 1, id: i })
+    }
+    tree.load(items)
+    tree.freezeRegion({ minX: -1, minY: -1, maxX: 60, maxY: 60 })
+
+    const outside = { minX: 150, minY: 150, maxX: 151, maxY: 151 }
+    tree.insert(outside)
+    tree.remove(outside)
+    tree.remove(items[399])
+
+    const frozen = items.filter((item) => item.maxX <= 60 && item.maxY <= 60)
+    const results = tree.search({ minX: -1, minY: -1, maxX: 60, maxY: 60 })
+    expect(results.length).toBe(frozen.length)
+  })
+})
+
+describe("RBush Remove With", () => {
+  test("removeWith matches stored items through the callback", () => {
+    const tree = new RBushWasm(4)
+    const items = []
+    for (let i = 0; i < 50; i++) items.push({ minX: i, minY: 0, maxX: i + 1, maxY: 1, id: i })
+    tree.load(items)
+
+    const sameId = (candidate, item) => candidate.id === item.id
+    tree.remove({ ...items[10] })
+    expect(tree.len()).toBe(50)
+    tree.removeWith({ ...items[10] }, sameId)
+    expect(tree.len()).toBe(49)
+    expect(tree.search(items[10]).map((item) => item.id)).not.toContain(10)
+
+    expect(() =>
+      tree.removeWith({ ...items[20] }, () => {
+        throw new Error("boom")
+      })
+    ).toThrow("boom")
+    expect(tree.len()).toBe(49)
+  })
+})
+
+describe("RBush Remove All", () => {
+  test("removeAll matches a loop of remove calls", () => {
+    const items = []
+    for (let i = 0; i < 3000; i++) {
+      const x = Math.random() * 1000
+      const y = Math.random() * 1000
+      items.push({ minX: x, minY: y, maxX: x + Math.random() * 10, maxY: y + Math.random() * 10, id: i })
+    }
+    const stale = items.filter((_, i) => i % 3 === 0)
+    const missing = { minX: 1, minY: 1, maxX: 2, maxY: 2, id: -1 }
+
+    const tree = new RBushWasm(9)
+    tree.load(items)
+    expect(tree.removeAll([...stale, missing])).toBe(stale.length)
+    expect(tree.len()).toBe(items.length - stale.length)
+
+    const kept = items.filter((_, i) => i % 3 !== 0).map((item) => item.id)
+    const ids = tree.all().map((item) => item.id).sort((a, b) => a - b)
+    expect(ids).toEqual(kept)
+  })
+})
+
+describe("RBush Search Predicate", () => {
+  test("searchPredicate filters hits on geometry and data together", () => {
+    const tree = new RBushWasm(4)
+    const items = []
+    for (let i = 0; i < 200; i++) {
+      const x = (i % 20) * 5
+      const y = Math.floor(i / 20) * 5
+      items.push({ minX: x, minY: y, maxX: x + (i % 4), maxY: y + 1, id: i })
+    }
+    tree.load(items)
+
+    const box = { minX: 10, minY: 10, maxX: 60, maxY: 40 }
+    const keep = (item, minX, minY, maxX) => item.id % 2 === 0 && maxX - minX >= 2
+    const ids = (list) => list.map((item) => item.id).sort((a, b) => a - b)
+
+    const expected = tree.search(box).filter((item) => keep(item, item.minX, item.minY, item.maxX))
+    expect(ids(tree.searchPredicate(box, keep))).toEqual(ids(expected))
+    expect(ids(tree.searchPredicate(box))).toEqual(ids(tree.search(box)))
+    expect(() =>
+      tree.searchPredicate(box, () => {
+        throw new Error("boom")
+      })
+    ).toThrow("boom")
+  })
+})
+
+describe("RBush Search Each", () => {
+  test("searchEach visits the same items as search and stops on false", () => {
+    const tree = new RBushWasm(4)
+    const items = []
+    for (let i = 0; i < 500; i++) {
+      const x = Math.random() * 100
+      const y = Math.random() * 100
+      items.push({ minX: x, minY: y, maxX: x + 2, maxY: y + 2, id: i })
+    }
+    tree.load(items)
+
+    const box = { minX: 20, minY: 20, maxX: 70, maxY: 70 }
+    const visited = []
+    tree.searchEach(box, (item) => {
+      visited.push(item.id)
+    })
+    const expected = tree.search(box).map((item) => item.id)
+    expect(visited.sort((a, b) => a - b)).toEqual(expected.sort((a, b) => a - b))
+
+    const firstThree = []
+    tree.searchEach(box, (item) => {
+      firstThree.push(item)
+      return firstThree.length < 3
+    })
+    expect(firstThree.length).toBe(Math.min(3, expected.length))
+  })
+})
+
+describe("RBush Partition Items", () => {
+  test("partitionItems returns n balanced chunks covering every item once", () => {
+    const tree = new RBushWasm(9)
+    const items = []
+    for (let i = 0; i < 1003; i++) {
+      const x = Math.random() * 1000
+      const y = Math.random() * 1000
+      items.push({ minX: x, minY: y, maxX: x + 1, maxY: y + 1, id: i })
+    }
+    tree.load(items)
+
+    const chunks = tree.partitionItems(4)
+    expect(chunks.length).toBe(4)
+    for (const chunk of chunks) {
+      expect(chunk.length === 250 || chunk.length === 251).toBe(true)
+    }
+    const ids = chunks.flat().map((item) => item.id).sort((a, b) => a - b)
+    expect(ids).toEqual(items.map((item) => item.id))
+
+    expect(tree.partitionItems(0)).toEqual([])
+    expect(new RBushWasm().partitionItems(3)).toEqual([[], [], []])
+  })
+})
+
+describe("RBush Search Path", () => {
+  test("searchPath returns each item near the polyline once", () => {
+    const tree = new RBushWasm(4)
+    const items = []
+    for (let x = 0; x < 20; x++) {
+      for (let y = 0; y < 20; y++) {
+        items.push({ minX: x * 5, minY: y * 5, maxX: x * 5 + 1, maxY: y * 5 + 1, id: `${x},${y}` })
+      }
+    }
+    tree.load(items)
+
+    // L-shaped path along y = 0.5 then x = 50.5
+    const path = new Float64Array([0, 0.5, 50.5, 0.5, 50.5, 50])
+    const ids = tree.searchPath(path, 1).map((item) => item.id)
+    const expected = items
+      .filter((item) => (item.minY === 0 && item.minX <= 51) || (item.minX === 50 && item.minY <= 50))
+      .map((item) => item.id)
+    expect(new Set(ids).size).toBe(ids.length)
+    expect(ids.sort()).toEqual(expected.sort())
+
+    // the diagonal touches no box; its endpoints are ~1.8 from the nearest
+    // two boxes and the third-nearest box is ~2.47 away
+    const diagonal = new Float64Array([2, 3.5, 3.5, 2])
+    expect(tree.searchPath(diagonal, 1.5)).toEqual([])
+    expect(tree.searchPath(diagonal, 2).map((item) => item.id).sort()).toEqual(["0,1", "1,0"])
+  })
+})
+
+describe("RBush Insert With Box", () => {
+  test("insertWithBox stores payloads without bbox fields", () => {
+    const tree = new RBushWasm(4)
+    for (let i = 0; i < 100; i++) {
+      tree.insertWithBox(i, i, i + 1, i + 1, `item-${i}`)
+    }
+    const tagged = { kind: "shop" }
+    tree.insertWithBox(5, 5, 6, 6, tagged)
+    expect(tree.len()).toBe(101)
+
+    expect(tree.search({ minX: 10.5, minY: 10.5, maxX: 11.5, maxY: 11.5 }).sort()).toEqual(["item-10", "item-11"])
+    expect(tree.search({ minX: 5.5, minY: 5.5, maxX: 5.5, maxY: 5.5 })).toContain(tagged)
+    expect(tree.all()).toContain("item-99")
+
+    tree.remove("item-10")
+    tree.remove(tagged)
+    expect(tree.len()).toBe(99)
+    expect(tree.search({ minX: 10.5, minY: 10.5, maxX: 10.5, maxY: 10.5 })).toEqual([])
+    expect(tree.search({ minX: 5.5, minY: 5.5, maxX: 5.5, maxY: 5.5 })).toEqual(["item-5"])
+  })
+})
+
+describe("RBush Search Buffer", () => {
+  test("searchBuffer packs the search matches into 40-byte records", () => {
+    const tree = new RBushWasm(4)
+    const items = []
+    for (let i = 0; i < 300; i++) {
+      const x = Math.random() * 100
+      const y = Math.random() * 100
+      items.push({ minX: x, minY: y, maxX: x + 3, maxY: y + 2, id: i })
+    }
+    tree.load(items)
+
+    const box = { minX: 20, minY: 20, maxX: 60, maxY: 60 }
+    const { buffer, data } = tree.searchBuffer(box)
+    expect(buffer).toBeInstanceOf(ArrayBuffer)
+    expect(buffer.byteLength).toBe(data.length * 40)
+    expect(data.map((item) => item.id).sort((a, b) => a - b)).toEqual(
+      tree.search(box).map((item) => item.id).sort((a, b) => a - b)
+    )
+
+    const view = new DataView(buffer)
+    for (let i = 0; i < data.length; i++) {
+      const item = data[view.getUint32(i * 40 + 32, true)]
+      expect(view.getFloat64(i * 40, true)).toBe(item.minX)
+      expect(view.getFloat64(i * 40 + 8, true)).toBe(item.minY)
+      expect(view.getFloat64(i * 40 + 16, true)).toBe(item.maxX)
+      expect(view.getFloat64(i * 40 + 24, true)).toBe(item.maxY)
+    }
+  })
+})
+
+describe("RBush Diagnostics", () => {
+  test("height, nodeCount and depthHistogram describe the tree shape", () => {
+    const empty = new RBushWasm(4)
+    expect(empty.height()).toBe(1)
+    expect(empty.nodeCount()).toBe(1)
+    expect(empty.depthHistogram()).toEqual([1])
+
+    const tree = new RBushWasm(4)
+    for (let i = 0; i < 500; i++) {
+      const x = Math.random() * 100
+      const y = Math.random() * 100
+      tree.insert({ minX: x, minY: y, maxX: x + 1, maxY: y + 1 })
+    }
+    const js = tree.toJSON()
+    let nodes = 0
+    let leaves = 0
+    const walk = (node) => {
+      nodes++
+      if (node.leaf) leaves++
+      else node.children.forEach(walk)
+    }
+    walk(js)
+
+    expect(tree.height()).toBe(js.height)
+    expect(tree.nodeCount()).toBe(nodes)
+    const histogram = tree.depthHistogram()
+    expect(histogram.length).toBe(tree.height())
+    expect(histogram[histogram.length - 1]).toBe(leaves)
+  })
+})
+
+describe("RBush Clone", () => {
+  test("clone branches the tree so edits stay on one side", () => {
+    const tree = new RBushWasm(4)
+    const items = []
+    for (let i = 0; i < 200; i++) items.push({ minX: i, minY: i, maxX: i + 1, maxY: i + 1, id: i })
+    tree.load(items)
+
+    const copy = tree.clone()
+    const box = { minX: 0, minY: 0, maxX: 300, maxY: 300 }
+    tree.insert({ minX: 250, minY: 250, maxX: 251, maxY: 251, id: "new" })
+    tree.remove(items[5])
+    copy.remove(items[6])
+
+    expect(tree.search(box).length).toBe(200)
+    expect(copy.search(box).length).toBe(199)
+    expect(copy.search(items[5])).toContain(items[5])
+    expect(copy.search({ minX: 250, minY: 250, maxX: 250, maxY: 250 })).toEqual([])
+    expect(tree.search(items[6])).toContain(items[6])
+  })
+})
+
+describe("RBush Would Split", () => {
+  test("wouldSplit predicts whether the next insert splits a node", () => {
+    const tree = new RBushWasm(4)
+    const item = (i) => ({ minX: i, minY: 0, maxX: i + 1, maxY: 1 })
+    for (let i = 0; i < 3; i++) tree.insert(item(i))
+    expect(tree.wouldSplit(item(3))).toBe(false)
+    tree.insert(item(3))
+    expect(tree.height()).toBe(1)
+    expect(tree.wouldSplit(item(4))).toBe(true)
+    tree.insert(item(4))
+    expect(tree.height()).toBe(2)
+
+    for (let i = 5; i < 300; i++) {
+      const next = item(i % 37)
+      const before = tree.nodeCount()
+      const predicted = tree.wouldSplit(next)
+      tree.insert(next)
+      expect(tree.nodeCount() > before).toBe(predicted)
+    }
+  })
+})
+
+describe("RBush Search Radius", () => {
+  test("searchRadius matches a brute-force disc test", () => {
+    const tree = new RBushWasm(6)
+    const items = []
+    for (let i = 0; i < 1000; i++) {
+      const x = Math.random() * 100
+      const y = Math.random() * 100
+      items.push({ minX: x, minY: y, maxX: x + Math.random() * 3, maxY: y + Math.random() * 3, id: i })
+    }
+    tree.load(items)
+
+    const [cx, cy, r] = [50, 50, 12]
+    const gap = (min, max, v) => Math.max(0, min - v, v - max)
+    const expected = items
+      .filter((item) => Math.hypot(gap(item.minX, item.maxX, cx), gap(item.minY, item.maxY, cy)) <= r)
+      .map((item) => item.id)
+      .sort((a, b) => a - b)
+    const ids = tree.searchRadius(cx, cy, r).map((item) => item.id).sort((a, b) => a - b)
+    expect(ids).toEqual(expected)
+
+    // a box touching only the square's corner is excluded
+    const corner = new RBushWasm()
+    corner.insert({ minX: 9, minY: 9, maxX: 10, maxY: 10 })
+    expect(corner.searchRadius(0, 0, 10)).toEqual([])
+    expect(corner.search({ minX: -10, minY: -10, maxX: 10, maxY: 10 }).length).toBe(1)
+  })
+})
+
+describe("RBush Search Entries", () => {
+  test("searchEntries pairs each hit's bbox with its data", () => {
+    const tree = new RBushWasm(4)
+    for (let i = 0; i < 50; i++) tree.insertWithBox(i * 2, 0, i * 2 + 1, 1, `id-${i}`)
+
+    const entries = tree.searchEntries({ minX: 10, minY: 0, maxX: 14, maxY: 1 })
+    entries.sort((a, b) => a.minX - b.minX)
+    expect(entries).toEqual([
+      { minX: 10, minY: 0, maxX: 11, maxY: 1, data: "id-5" },
+      { minX: 12, minY: 0, maxX: 13, maxY: 1, data: "id-6" },
+      { minX: 14, minY: 0, maxX: 15, maxY: 1, data: "id-7" },
+    ])
+  })
+})
+
+describe("RBush Fill Factor", () => {
+  test("fillFactor changes node fill without changing results", () => {
+    const items = []
+    for (let i = 0; i < 2000; i++) {
+      const x = Math.random() * 1000
+      const y = Math.random() * 1000
+      items.push({ minX: x, minY: y, maxX: x + 5, maxY: y + 5, id: i })
+    }
+    const box = { minX: 200, minY: 200, maxX: 700, maxY: 700 }
+    const ids = (tree) => tree.search(box).map((item) => item.id).sort((a, b) => a - b)
+
+    const sparse = new RBushWasm(16, 0, 0.1)
+    const dense = new RBushWasm(16, 0, 0.5)
+    const clamped = new RBushWasm(16, 0, 3)
+    for (const tree of [sparse, dense, clamped]) {
+      items.forEach((item) => tree.insert(item))
+      items.slice(0, 1500).forEach((item) => tree.remove(item))
+    }
+    expect(ids(sparse)).toEqual(ids(dense))
+    expect(ids(clamped)).toEqual(ids(dense))
+    expect(sparse.len()).toBe(500)
+    expect(dense.opStats().reinserts).toBeGreaterThan(sparse.opStats().reinserts)
+  })
+})
+
+describe("RBush Load Flat", () => {
+  test("loadFlat stores numeric ids and checks the lengths", () => {
+    const count = 1000
+    const coords = new Float64Array(count * 4)
+    const ids = new Float64Array(count)
+    for (let i = 0; i < count; i++) {
+      coords.set([i % 100, Math.floor(i / 100), (i % 100) + 0.5, Math.floor(i / 100) + 0.5], i * 4)
+      ids[i] = i
+    }
+
+    const tree = new RBushWasm(9)
+    tree.loadFlat(coords, ids)
+    expect(tree.len()).toBe(count)
+    expect(tree.search({ minX: 3, minY: 2, maxX: 3.2, maxY: 2.2 })).toEqual([203])
+
+    tree.remove(203)
+    expect(tree.len()).toBe(count - 1)
+    expect(tree.search({ minX: 3, minY: 2, maxX: 3.2, maxY: 2.2 })).toEqual([])
+
+    expect(() => tree.loadFlat(new Float64Array(7), new Float64Array(2))).toThrow()
+    expect(tree.len()).toBe(count - 1)
+  })
+})
+
+describe("RBush Strict Mode", () => {
+  test("setStrict rejects malformed boxes with the field name", () => {
+    const tree = new RBushWasm()
+    tree.insert({ minX: 0, minY: 0, maxx: 1, maxY: 1 })
+    expect(tree.len()).toBe(1)
+
+    tree.clear()
+    tree.setStrict(true)
+    expect(() => tree.insert({ minX: 0, minY: 0, maxx: 1, maxY: 1 })).toThrow("maxX")
+    expect(() => tree.insert({ minX: 0, minY: "a", maxX: 1, maxY: 1 })).toThrow("minY")
+    expect(() => tree.insert({ minX: 2, minY: 0, maxX: 1, maxY: 1 })).toThrow("maxX")
+    expect(() => tree.load([{ minX: 0, minY: 0, maxX: 1, maxY: 1 }, { minX: 0 }])).toThrow("minY")
+    expect(() => tree.search({ minX: 0, minY: 0, maxX: 1 })).toThrow("maxY")
+    expect(() => tree.collides({ minX: 0, minY: 0, maxX: 1, maxY: -1 })).toThrow("maxY")
+    expect(tree.len()).toBe(0)
+
+    const item = { minX: 0, minY: 0, maxX: 1, maxY: 1 }
+    tree.insert(item)
+    expect(tree.search({ minX: 0, minY: 0, maxX: 2, maxY: 2 })).toEqual([item])
+    tree.insertWithBox(5, 5, 6, 6, "id")
+    tree.remove("id")
+    expect(tree.len()).toBe(1)
+  })
+
+  const bad = { minX: 0, minY: 0, maxx: 1, maxY: 1 }
+
+  test("strict mode covers every insert variant", () => {
+    const tree = new RBushWasm()
+    tree.setStrict(true)
+    expect(() => tree.insertAtTime(bad, 1)).toThrow("maxX")
+    expect(() => tree.insertTaggedBits(bad, 1)).toThrow("maxX")
+    expect(() => tree.insertPadded(bad, 1)).toThrow("maxX")
+    expect(() => tree.insertPinned(bad)).toThrow("maxX")
+    expect(() => tree.insertMany([bad])).toThrow("maxX")
+    expect(tree.len()).toBe(0)
+
+    const item = { minX: 0, minY: 0, maxX: 1, maxY: 1 }
+    tree.insertPadded(item, 1)
+    delete item.maxX
+    expect(() => tree.updatePadded(item)).toThrow("maxX")
+  })
+
+  test("strict mode covers every search variant", () => {
+    const tree = new RBushWasm()
+    tree.load([{ minX: 0, minY: 0, maxX: 1, maxY: 1 }])
+    tree.setStrict(true)
+    expect(() => tree.searchCursor(bad)).toThrow("maxX")
+    expect(() => tree.searchBfs(bad)).toThrow("maxX")
+    expect(() => tree.searchAdaptive(bad, 1)).toThrow("maxX")
+    expect(() => tree.searchEach(bad, () => {})).toThrow("maxX")
+    expect(() => tree.searchBounds(bad)).toThrow("maxX")
+    expect(() => tree.maxOverlap(bad)).toThrow("maxX")
+    expect(() => tree.knnBox(bad, 1)).toThrow("maxX")
+    expect(() => tree.estimateCount(bad)).toThrow("maxX")
+    expect(tree.searchBfs({ minX: 0, minY: 0, maxX: 1, maxY: 1 })).toHaveLength(1)
+  })
+
+  test("strict mode covers every removal variant", () => {
+    const tree = new RBushWasm()
+    const item = { minX: 0, minY: 0, maxX: 1, maxY: 1 }
+    tree.insert(item)
+    tree.setStrict(true)
+    delete item.maxX
+    expect(() => tree.remove(item)).toThrow("maxX")
+    expect(() => tree.removeAll([item])).toThrow("maxX")
+    expect(() => tree.removeWith(item, (a, b) => a === b)).toThrow("maxX")
+    expect(() => tree.update(item, 2, 2, 3, 3)).toThrow("maxX")
+    expect(tree.len()).toBe(1)
+  })
+
+  test("strict mode covers fromJSON and insertLevel", () => {
+    const tree = new RBushWasm()
+    const json = { children: [bad], height: 1, leaf: true, minX: 0, minY: 0, maxX: 1, maxY: 1 }
+    tree.setStrict(true)
+    expect(() => tree.fromJSON(json)).toThrow("maxX")
+    expect(() => tree.insertLevel([json], 1)).toThrow("maxX")
+    expect(tree.len()).toBe(0)
+  })
+})
+
+describe("RBush Self Intersections", () => {
+  test("selfIntersections matches brute force with each pair once", () => {
+    const tree = new RBushWasm(5)
+    const items = []
+    for (let i = 0; i < 400; i++) {
+      const x = Math.random() * 200
+      const y = Math.random() * 200
+      items.push({ minX: x, minY: y, maxX: x + Math.random() * 8, maxY: y + Math.random() * 8, id: i })
+    }
+    tree.load(items)
+
+    const key = (a, b) => (a < b ? `${a}-${b}` : `${b}-${a}`)
+    const expected = []
+    for (let i = 0; i < items.length; i++) {
+      for (let j = i + 1; j < items.length; j++) {
+        const [a, b] = [items[i], items[j]]
+        if (a.minX <= b.maxX && b.minX <= a.maxX && a.minY <= b.maxY && b.minY <= a.maxY) {
+          expected.push(key(a.id, b.id))
+        }
+      }
+    }
+
+    const pairs = tree.selfIntersections()
+    expect(pairs.every(([a, b]) => a !== b)).toBe(true)
+    const keys = pairs.map(([a, b]) => key(a.id, b.id))
+    expect(new Set(keys).size).toBe(keys.length)
+    expect(keys.sort()).toEqual(expected.sort())
+  })
+})
+
+describe("RBush Entry Limits", () => {
+  test("maxEntries and minEntries report the configured fanout", () => {
+    expect(new RBushWasm().maxEntries).toBe(9)
+    expect(new RBushWasm().minEntries).toBe(4)
+    expect(new RBushWasm(2).maxEntries).toBe(4)
+
+    const tree = new RBushWasm(16, 0, 0.25)
+    expect(tree.maxEntries).toBe(16)
+    expect(tree.minEntries).toBe(4)
+    const copy = new RBushWasm(tree.maxEntries, 0, tree.minEntries / tree.maxEntries)
+    expect(copy.minEntries).toBe(tree.minEntries)
+  })
+})
+
+describe("RBush 3D", () => {
+  const { RBush3D } = require("./pkg/rbush_rs")
+
+  test("RBush3D prunes on all three axes and matches brute force", () => {
+    const items = []
+    for (let i = 0; i < 3000; i++) {
+      const [x, y, z] = [Math.random() * 100, Math.random() * 100, Math.random() * 100]
+      items.push({ minX: x, minY: y, minZ: z, maxX: x + 1, maxY: y + 1, maxZ: z + 1, id: i })
+    }
+    const tree = new RBush3D(8)
+    tree.load(items.slice(0, 2000))
+    items.slice(2000).forEach((item) => tree.insert(item))
+    items.filter((_, i) => i % 3 === 0).forEach((item) => tree.remove(item))
+    const kept = items.filter((_, i) => i % 3 !== 0)
+    expect(tree.len()).toBe(kept.length)
+
+    const box = { minX: 20, minY: 20, minZ: 40, maxX: 60, maxY: 60, maxZ: 45 }
+    const hit = (a) =>
+      a.minX <= box.maxX && a.maxX >= box.minX &&
+      a.minY <= box.maxY && a.maxY >= box.minY &&
+      a.minZ <= box.maxZ && a.maxZ >= box.minZ
+    const ids = (list) => list.map((item) => item.id).sort((a, b) => a - b)
+    expect(ids(tree.search(box))).toEqual(ids(kept.filter(hit)))
+    expect(tree.collides(box)).toBe(kept.some(hit))
+
+    // overlapping in x and y but not in z
+    expect(tree.search({ minX: 0, minY: 0, minZ: -10, maxX: 100, maxY: 100, maxZ: -5 })).toEqual([])
+  })
+})
+
+describe("RBush Search Point", () => {
+  test("searchPoint matches a zero-size search, edges included", () => {
+    const tree = new RBushWasm(4)
+    const items = []
+    for (let i = 0; i < 500; i++) {
+      const x = Math.floor(Math.random() * 50)
+      const y = Math.floor(Math.random() * 50)
+      items.push({ minX: x, minY: y, maxX: x + 3, maxY: y + 3, id: i })
+    }
+    tree.load(items)
+
+    for (const [x, y] of [[10, 10], [3, 47.5], [0, 0], [52.9, 25]]) {
+      const ids = tree.searchPoint(x, y).map((item) => item.id).sort((a, b) => a - b)
+      const expected = items
+        .filter((item) => item.minX <= x && x <= item.maxX && item.minY <= y && y <= item.maxY)
+        .map((item) => item.id)
+        .sort((a, b) => a - b)
+      expect(ids).toEqual(expected)
+    }
+  })
+})
+
+describe("RBush Search Cursor", () => {
+  test("searchCursor streams the search results in batches", () => {
+    const tree = new RBushWasm(6)
+    const items = []
+    for (let i = 0; i < 2000; i++) {
+      const x = Math.random() * 100
+      const y = Math.random() * 100
+      items.push({ minX: x, minY: y, maxX: x + 1, maxY: y + 1, id: i })
+    }
+    tree.load(items)
+
+    const box = { minX: 10, minY: 10, maxX: 80, maxY: 80 }
+    const cursor = tree.searchCursor(box)
+    const streamed = []
+    for (;;) {
+      const batch = cursor.nextBatch(tree, 100)
+      expect(batch.length).toBeLessThanOrEqual(100)
+      if (batch.length === 0) break
+      streamed.push(...batch)
+    }
+    expect(cursor.done()).toBe(true)
+    const ids = (list) => list.map((item) => item.id).sort((a, b) => a - b)
+    expect(streamed.length).toBe(tree.search(box).length)
+    expect(ids(streamed)).toEqual(ids(tree.search(box)))
+
+    expect(tree.searchCursor({ minX: 500, minY: 500, maxX: 600, maxY: 600 }).done()).toBe(true)
+  })
+
+  test("nextBatch rejects a tree other than the cursor's own", () => {
+    const tree = new RBushWasm(4)
+    const other = new RBushWasm(4)
+    for (let i = 0; i < 100; i++) {
+      tree.insert({ minX: i, minY: 0, maxX: i, maxY: 0, id: i })
+      other.insert({ minX: i, minY: 0, maxX: i, maxY: 0, id: -i })
+    }
+    const box = { minX: 0, minY: 0, maxX: 100, maxY: 0 }
+    const cursor = tree.searchCursor(box)
+    const first = cursor.nextBatch(tree, 10)
+    expect(first.length).toBe(10)
+
+    expect(() => cursor.nextBatch(other, 10)).toThrow("another tree")
+    expect(() => cursor.nextBatch(tree.clone(), 10)).toThrow("another tree")
+
+    // the failed calls consumed nothing
+    const rest = []
+    for (let batch; (batch = cursor.nextBatch(tree, 30)).length > 0; ) rest.push(...batch)
+    expect([...first, ...rest].map((item) => item.id).sort((a, b) => a - b)).toEqual(
+      Array.from({ length: 100 }, (_, i) => i)
+    )
+  })
+})
+
+describe("RBush Update", () => {
+  test("update moves items in place or through a reinsert", () => {
+    const tree = new RBushWasm(4)
+    const items = []
+    for (let i = 0; i < 100; i++) {
+      items.push({ minX: i, minY: i, maxX: i + 1, maxY: i + 1, id: i })
+    }
+    tree.load(items)
+
+    const near = items[10]
+    expect(tree.update(near, 10.2, 10.2, 10.8, 10.8)).toBe(true)
+    const far = items[20]
+    expect(tree.update(far, 500, 500, 501, 501)).toBe(true)
+    expect(tree.len()).toBe(100)
+
+    expect(tree.search({ minX: 10.1, minY: 10.1, maxX: 10.9, maxY: 10.9 })).toContain(near)
+    expect(tree.search({ minX: 499, minY: 499, maxX: 502, maxY: 502 })).toEqual([far])
+    expect(tree.search({ minX: 20.2, minY: 20.2, maxX: 20.8, maxY: 20.8 })).not.toContain(far)
+
+    expect(tree.update({ minX: 0, minY: 0, maxX: 1, maxY: 1 }, 0, 0, 1, 1)).toBe(false)
+  })
+
+  test("a reinserting update counts as neither a removal nor an insert", () => {
+    const tree = new RBushWasm(4, 1)
+    const items = []
+    for (let i = 0; i < 100; i++) items.push({ minX: i, minY: i, maxX: i + 1, maxY: i + 1 })
+    tree.load(items)
+    const before = tree.opStats()
+
+    const item = items[20]
+    expect(tree.update(item, 500, 500, 501, 501)).toBe(true)
+    const after = tree.opStats()
+    expect(after.inserts).toBe(before.inserts)
+    expect(after.removals).toBe(before.removals)
+    expect(tree.pendingRemovals()).toBe(0)
+
+    // the item's own fields still hold the old box, so remove can't find it
+    tree.remove(item)
+    expect(tree.len()).toBe(100)
+    Object.assign(item, { minX: 500, minY: 500, maxX: 501, maxY: 501 })
+    tree.remove(item)
+    expect(tree.len()).toBe(99)
+  })
+
+  test("a padded item keeps its padding through update", () => {
+    // in place: the padded new box still fits the root leaf node
+    const tree = new RBushWasm()
+    tree.insert({ minX: 0, minY: 0, maxX: 100, maxY: 100 })
+    const item = { minX: 10, minY: 10, maxX: 12, maxY: 12 }
+    tree.insertPadded(item, 5)
+    expect(tree.update(item, 20, 20, 22, 22)).toBe(true)
+    Object.assign(item, { minX: 23, minY: 20, maxX: 25, maxY: 22 })
+    expect(tree.updatePadded(item)).toBe(true)
+    expect(tree.search({ minX: 26, minY: 20, maxX: 27, maxY: 22 })).not.toContain(item)
+
+    // through a reinsert: the lone item's padded box is the root's bbox
+    const lone = new RBushWasm()
+    const moved = { minX: 0, minY: 0, maxX: 2, maxY: 2 }
+    lone.insertPadded(moved, 5)
+    expect(lone.update(moved, 50, 50, 52, 52)).toBe(true)
+    expect(lone.toJSON()).toMatchObject({ minX: 45, minY: 45, maxX: 57, maxY: 57 })
+    Object.assign(moved, { minX: 53, minY: 53, maxX: 55, maxY: 55 })
+    expect(lone.updatePadded(moved)).toBe(true)
+    expect(lone.search({ minX: 53, minY: 53, maxX: 54, maxY: 54 })).toEqual([moved])
+  })
+})
+
+describe("RBush Stats", () => {
+  test("stats summarizes node counts and sibling overlap", () => {
+    const empty = new RBushWasm(4).stats()
+    expect(empty).toEqual({ leafNodes: 1, internalNodes: 0, avgChildren: 0, height: 1, overlapArea: 0 })
+
+    const tree = new RBushWasm(4)
+    const items = []
+    for (let i = 0; i < 64; i++) {
+      items.push({ minX: i, minY: i, maxX: i + 1, maxY: i + 1 })
+    }
+    tree.load(items)
+    const stats = tree.stats()
+    expect(stats.height).toBe(tree.height())
+    expect(stats.leafNodes + stats.internalNodes).toBe(tree.nodeCount())
+    expect(stats.leafNodes).toBe(16)
+    expect(stats.avgChildren).toBeCloseTo((64 + tree.nodeCount() - 1) / tree.nodeCount())
+    expect(stats.overlapArea).toBe(0)
+  })
+})
+
+describe("RBush Search Flat", () => {
+  test("searchFlat matches search with an equivalent object", () => {
+    const tree = new RBushWasm(4)
+    const items = []
+    for (let i = 0; i < 200; i++) {
+      const x = Math.random() * 100
+      const y = Math.random() * 100
+      items.push({ minX: x, minY: y, maxX: x + 2, maxY: y + 2, id: i })
+    }
+    tree.load(items)
+
+    const scratch = new Float64Array(4)
+    const ids = (list) => list.map((item) => item.id).sort((a, b) => a - b)
+    for (let q = 0; q < 20; q++) {
+      scratch.set([q * 4, q * 3, q * 4 + 20, q * 3 + 20])
+      const box = { minX: scratch[0], minY: scratch[1], maxX: scratch[2], maxY: scratch[3] }
+      expect(ids(tree.searchFlat(scratch))).toEqual(ids(tree.search(box)))
+    }
+
+    expect(() => tree.searchFlat(new Float64Array(3))).toThrow()
+  })
+})
+
+describe("RBush Nearest Where", () => {
+  test("nearestWhere returns the closest item passing the predicate", () => {
+    const tree = new RBushWasm(4)
+    const items = []
+    for (let i = 0; i < 100; i++) {
+      items.push({ minX: i, minY: 0, maxX: i, maxY: 0, open: i % 10 === 7 })
+    }
+    tree.load(items)
+
+    const seen = []
+    const hit = tree.nearestWhere(20, 0, (data) => {
+      seen.push(data)
+      return data.open
+    })
+    expect(hit).toBe(items[17])
+    expect(seen.length).toBeLessThan(20)
+
+    expect(tree.nearestWhere(20, 0, () => false)).toBeNull()
+    expect(() => tree.nearestWhere(20, 0, () => { throw new Error("boom") })).toThrow("boom")
+  })
+})
+
+describe("RBush Knn With Distance", () => {
+  test("knnWithDistance ranks by the callback's distance", () => {
+    const tree = new RBushWasm(4)
+    const items = []
+    for (let i = 0; i < 50; i++) {
+      items.push({ minX: i, minY: i % 5, maxX: i, maxY: i % 5, id: i })
+    }
+    tree.load(items)
+
+    // Gap along x only, so y is ignored.
+    const xGap = (x) => (minX, minY, maxX, maxY) => Math.max(minX - x, 0, x - maxX)
+    const result = tree.knnWithDistance(3, xGap(10))
+    expect(result.map((item) => item.id).sort((a, b) => a - b)).toEqual([9, 10, 11])
+
+    const euclidean = (x, y) => (minX, minY, maxX, maxY) => {
+      const dx = Math.max(minX - x, 0, x - maxX)
+      const dy = Math.max(minY - y, 0, y - maxY)
+      return Math.sqrt(dx * dx + dy * dy)
+    }
+    const ids = (list) => list.map((item) => item.id).sort((a, b) => a - b)
+    expect(ids(tree.knnWithDistance(4, euclidean(20, 3)))).toEqual(ids(tree.knn(20, 3, 4)))
+
+    expect(() => tree.knnWithDistance(1, () => "far")).toThrow()
+    expect(() => tree.knnWithDistance(1, () => { throw new Error("boom") })).toThrow("boom")
+  })
+
+  test("the callback gets exactly the four box coordinates", () => {
+    const tree = new RBushWasm(4)
+    tree.load([
+      { minX: 1, minY: 2, maxX: 3, maxY: 4 },
+      { minX: 5, minY: 6, maxX: 7, maxY: 8 },
+    ])
+    const calls = []
+    tree.knnWithDistance(2, (...args) => {
+      calls.push(args)
+      return args[0]
+    })
+    expect(calls.length).toBeGreaterThan(0)
+    expect(calls.every((args) => args.length === 4)).toBe(true)
+    expect(calls).toContainEqual([1, 2, 3, 4])
+    expect(calls).toContainEqual([5, 6, 7, 8])
+  })
+})
+
+describe("RBush Rebuild", () => {
+  test("rebuild keeps the items and restores a minimal height", () => {
+    const tree = new RBushWasm(4)
+    const items = []
+    for (let i = 0; i < 300; i++) {
+      const x = Math.random() * 100
+      const y = Math.random() * 100
+      const item = { minX: x, minY: y, maxX: x + 1, maxY: y + 1, id: i }
+      items.push(item)
+      tree.insert(item)
+    }
+    for (const item of items.splice(0, 44)) {
+      tree.remove(item)
+    }
+
+    const box = { minX: 20, minY: 20, maxX: 70, maxY: 70 }
+    const ids = (list) => list.map((item) => item.id).sort((a, b) => a - b)
+    const before = ids(tree.search(box))
+    tree.rebuild()
+    expect(ids(tree.search(box))).toEqual(before)
+    expect(tree.len()).toBe(256)
+    expect(tree.height()).toBe(4)
+  })
+})
+
+describe("RBush All Ordered", () => {
+  test("allOrdered returns every item in a repeatable order", () => {
+    const items = []
+    for (let i = 0; i < 100; i++) {
+      items.push({ minX: i, minY: i, maxX: i + 1, maxY: i + 1, id: i })
+    }
+    const a = new RBushWasm(4)
+    a.load(items)
+    const b = new RBushWasm(4)
+    b.load(items)
+
+    const ids = a.allOrdered().map((item) => item.id)
+    expect(ids).toEqual(b.allOrdered().map((item) => item.id))
+    expect([...ids].sort((x, y) => x - y)).toEqual(items.map((item) => item.id))
+  })
+})
+
+describe("RBush Insert Many", () => {
+  test("insertMany matches a loop over insert", () => {
+    const items = []
+    for (let i = 0; i < 100; i++) {
+      items.push({ minX: i, minY: i % 7, maxX: i + 2, maxY: (i % 7) + 2, id: i })
+    }
+    const batched = new RBushWasm(4)
+    batched.insertMany(items)
+    const looped = new RBushWasm(4)
+    for (const item of items) looped.insert(item)
+
+    expect(batched.len()).toBe(100)
+    expect(batched.toJSON()).toEqual(looped.toJSON())
+
+    const strict = new RBushWasm(4)
+    strict.setStrict(true)
+    expect(() => strict.insertMany([items[0], { minX: 0 }, items[1]])).toThrow()
+    expect(strict.len()).toBe(1)
+  })
+})
+
+describe("RBush NaN Coordinates", () => {
+  test("NaN boxes don't break inserts, loads or splits", () => {
+    const items = []
+    for (let i = 0; i < 200; i++) {
+      const x = i % 9 === 0 ? NaN : i
+      items.push({ minX: x, minY: i % 4 === 0 ? NaN : 0, maxX: x + 1, maxY: 1, id: i })
+    }
+    const loaded = new RBushWasm(4)
+    loaded.load(items)
+    const inserted = new RBushWasm(4)
+    for (const item of items) inserted.insert(item)
+
+    const valid = items.filter((item) => !isNaN(item.minX) && !isNaN(item.minY))
+    const ids = (list) => list.map((item) => item.id).sort((a, b) => a - b)
+    for (const tree of [loaded, inserted]) {
+      expect(tree.len()).toBe(200)
+      expect(ids(tree.search({ minX: -1, minY: -1, maxX: 300, maxY: 2 }))).toEqual(ids(valid))
+    }
+
+    const strict = new RBushWasm(4)
+    strict.setStrict(true)
+    expect(() => strict.insert({ minX: NaN, minY: 0, maxX: 1, maxY: 1 })).toThrow("minX")
+  })
+})
+
+describe("RBush Search Contained", () => {
+  test("searchContained returns only items inside the box", () => {
+    const tree = new RBushWasm(4)
+    const items = []
+    for (let i = 0; i < 200; i++) {
+      const x = Math.random() * 100
+      const y = Math.random() * 100
+      items.push({ minX: x, minY: y, maxX: x + 5, maxY: y + 5, id: i })
+    }
+    tree.load(items)
+
+    const box = { minX: 20, minY: 20, maxX: 60, maxY: 60 }
+    const inside = (item) =>
+      item.minX >= box.minX && item.minY >= box.minY && item.maxX <= box.maxX && item.maxY <= box.maxY
+    const ids = (list) => list.map((item) => item.id).sort((a, b) => a - b)
+    expect(ids(tree.searchContained(box))).toEqual(ids(items.filter(inside)))
+    expect(tree.searchContained(box).length).toBeLessThan(tree.search(box).length)
+
+    expect(tree.searchContained({ minX: -10, minY: -10, maxX: 200, maxY: 200 }).length).toBe(200)
+  })
+})
+
+describe("RBush Integer Coordinates", () => {
+  const { RBushI32 } = require("./pkg/rbush_rs")
+
+  test("RBushI32 matches brute force on grid data", () => {
+    const items = []
+    for (let i = 0; i < 1000; i++) {
+      const x = Math.floor(Math.random() * 500)
+      const y = Math.floor(Math.random() * 500)
+      items.push({ minX: x, minY: y, maxX: x + 4, maxY: y + 3, id: i })
+    }
+    const tree = new RBushI32(8)
+    tree.load(items.slice(0, 500))
+    for (const item of items.slice(500)) tree.insert(item)
+    expect(tree.len()).toBe(1000)
+
+    for (const item of items.slice(0, 100)) tree.remove(item)
+    const left = items.slice(100)
+    expect(tree.len()).toBe(900)
+
+    const ids = (list) => list.map((item) => item.id).sort((a, b) => a - b)
+    const box = { minX: 100, minY: 100, maxX: 250, maxY: 300 }
+    const expected = left.filter(
+      (item) => item.minX <= box.maxX && item.maxX >= box.minX && item.minY <= box.maxY && item.maxY >= box.minY
+    )
+    expect(ids(tree.search(box))).toEqual(ids(expected))
+    expect(tree.collides(box)).toBe(expected.length > 0)
+  })
+
+  test("fractional coordinates widen to the enclosing integer box", () => {
+    const tree = new RBushI32()
+    const item = { minX: 1.5, minY: 1.5, maxX: 2.5, maxY: 2.5 }
+    tree.insert(item)
+    expect(tree.search({ minX: 3, minY: 3, maxX: 4, maxY: 4 })).toEqual([item])
+    expect(tree.search({ minX: 4, minY: 4, maxX: 5, maxY: 5 })).toEqual([])
+  })
+
+  test("missing, non-numeric or NaN coordinates are rejected, not read as 0", () => {
+    const tree = new RBushI32(4)
+    const good = { minX: 0, minY: 0, maxX: 1, maxY: 1 }
+    tree.insert(good)
+
+    expect(() => tree.insert({ minX: 0, minY: 0, maxX: 1 })).toThrow(/maxY/)
+    expect(() => tree.insert({ minX: "a", minY: 0, maxX: 1, maxY: 1 })).toThrow(/minX/)
+    expect(() => tree.insert({ minX: NaN, minY: 0, maxX: 1, maxY: 1 })).toThrow(/minX/)
+    expect(() => tree.load([good, { minX: 0, minY: NaN, maxX: 1, maxY: 1 }])).toThrow(/minY/)
+    expect(() => tree.search({ minX: 0, minY: 0, maxX: NaN, maxY: 1 })).toThrow(/maxX/)
+    expect(() => tree.collides({})).toThrow(/minX/)
+    expect(() => tree.remove({ id: 1 })).toThrow(/minX/)
+
+    expect(tree.len()).toBe(1)
+    expect(tree.search({ minX: 0, minY: 0, maxX: 0, maxY: 0 })).toEqual([good])
+  })
+
+  test("inverted boxes are rejected", () => {
+    const tree = new RBushI32(4)
+    const good = { minX: 0, minY: 0, maxX: 1, maxY: 1 }
+    tree.insert(good)
+
+    expect(() => tree.insert({ minX: 5, minY: 0, maxX: 2, maxY: 1 })).toThrow(/maxX/)
+    expect(() => tree.insert({ minX: 0, minY: 3, maxX: 1, maxY: 2.5 })).toThrow(/maxY/)
+    expect(() => tree.load([good, { minX: 1, minY: 1, maxX: 0, maxY: 0 }])).toThrow(/maxX/)
+    expect(() => tree.search({ minX: 10, minY: 0, maxX: 0, maxY: 1 })).toThrow(/maxX/)
+    expect(() => tree.collides({ minX: 0, minY: 10, maxX: 1, maxY: 0 })).toThrow(/maxY/)
+
+    expect(tree.len()).toBe(1)
+    // a degenerate box (max equal to min) is still fine
+    tree.insert({ minX: 4, minY: 4, maxX: 4, maxY: 4 })
+    expect(tree.len()).toBe(2)
+  })
+})
+
+describe("RBush All Boxes", () => {
+  test("allBoxes lines up with all()", () => {
+    const tree = new RBushWasm(4)
+    for (let i = 0; i < 150; i++) {
+      const x = Math.random() * 100
+      const y = Math.random() * 100
+      tree.insert({ minX: x, minY: y, maxX: x + 1, maxY: y + 2 })
+    }
+
+    const items = tree.all()
+    const boxes = tree.allBoxes()
+    expect(boxes).toBeInstanceOf(Float64Array)
+    expect(boxes.length).toBe(items.length * 4)
+    items.forEach((item, i) => {
+      expect(Array.from(boxes.subarray(i * 4, i * 4 + 4))).toEqual([item.minX, item.minY, item.maxX, item.maxY])
+    })
+
+    expect(new RBushWasm().allBoxes().length).toBe(0)
+  })
+})
+
+describe("RBush Level Sizes", () => {
+  test("counts nodes and items per depth, root first", () => {
+    const tree = new RBushWasm(4)
+    const items = []
+    for (let i = 0; i < 100; i++) {
+      items.push({ minX: i, minY: 0, maxX: i + 1, maxY: 1 })
+    }
+    tree.load(items)
+
+    const { nodesPerLevel, itemsPerLevel } = tree.levelSizes()
+    expect(nodesPerLevel).toBeInstanceOf(Uint32Array)
+    expect(nodesPerLevel.length).toBe(tree.height())
+    expect(itemsPerLevel.length).toBe(tree.height())
+    expect(nodesPerLevel[0]).toBe(1)
+
+    // depth d holds the nodes of height `tree.height() - d`
+    const byHeight = new Array(tree.height() + 1).fill(0)
+    tree.forEachNode((minX, minY, maxX, maxY, height) => {
+      byHeight[height]++
+    })
+    for (let level = 0; level < nodesPerLevel.length; level++) {
+      expect(nodesPerLevel[level]).toBe(byHeight[tree.height() - level])
+    }
+
+    const bottom = itemsPerLevel.length - 1
+    expect(itemsPerLevel[bottom]).toBe(100)
+    expect(Array.from(itemsPerLevel.slice(0, bottom)).every((n) => n === 0)).toBe(true)
+  })
+
+  test("a single-node tree is one level, and an empty tree none", () => {
+    const tree = new RBushWasm(9)
+    tree.insert({ minX: 0, minY: 0, maxX: 1, maxY: 1 })
+    tree.insert({ minX: 2, minY: 2, maxX: 3, maxY: 3 })
+    const sizes = tree.levelSizes()
+    expect(Array.from(sizes.nodesPerLevel)).toEqual([1])
+    expect(Array.from(sizes.itemsPerLevel)).toEqual([2])
+
+    const empty = new RBushWasm().levelSizes()
+    expect(empty.nodesPerLevel.length).toBe(0)
+    expect(empty.itemsPerLevel.length).toBe(0)
+  })
+})
+
+describe("RBush Skeleton", () => {
+  test("mirrors the node hierarchy without items", () => {
+    const tree = new RBushWasm(4)
+    const items = []
+    for (let i = 0; i < 50; i++) {
+      items.push({ minX: i, minY: i % 7, maxX: i + 2, maxY: (i % 7) + 1 })
+    }
+    tree.load(items)
+
+    const skeleton = tree.toSkeleton()
+    const json = tree.toJSON()
+    const compare = (skel, node) => {
+      expect([skel.minX, skel.minY, skel.maxX, skel.maxY]).toEqual([node.minX, node.minY, node.maxX, node.maxY])
+      expect(skel.height).toBe(node.height)
+      expect(skel.childCount).toBe(node.children.length)
+      expect(Object.keys(skel).sort()).toEqual(["childCount", "children", "height", "maxX", "maxY", "minX", "minY"])
+      if (node.height === 1) {
+        expect(skel.children).toEqual([])
+      } else {
+        expect(skel.children.length).toBe(node.children.length)
+        skel.children.forEach((child, i) => compare(child, node.children[i]))
+      }
+    }
+    compare(skeleton, json)
+
+    let leafItems = 0
+    const walk = (skel) => {
+      if (skel.height === 1) leafItems += skel.childCount
+      skel.children.forEach(walk)
+    }
+    walk(skeleton)
+    expect(leafItems).toBe(50)
+  })
+
+  test("an empty tree is a bare root", () => {
+    const skeleton = new RBushWasm().toSkeleton()
+    expect(skeleton).toEqual({
+      minX: Infinity,
+      minY: Infinity,
+      maxX: -Infinity,
+      maxY: -Infinity,
+      height: 1,
+      childCount: 0,
+      children: [],
+    })
+  })
+})
+
+describe("RBush Level Boxes", () => {
+  test("levelBoxes returns the node bboxes at each depth", () => {
+    const tree = new RBushWasm(4)
+    const items = []
+    for (let i = 0; i < 64; i++) {
+      items.push({ minX: i, minY: i, maxX: i + 1, maxY: i + 1 })
+    }
+    tree.load(items)
+    const { nodesPerLevel } = tree.levelSizes()
+
+    expect(Array.from(tree.levelBoxes(0))).toEqual([0, 0, 64, 64])
+    for (let level = 0; level < tree.height(); level++) {
+      const boxes = tree.levelBoxes(level)
+      expect(boxes.length).toBe(nodesPerLevel[level] * 4)
+      for (let i = 0; i < boxes.length; i += 4) {
+        expect(boxes[i]).toBeGreaterThanOrEqual(0)
+        expect(boxes[i + 2]).toBeLessThanOrEqual(64)
+      }
+    }
+
+    expect(tree.levelBoxes(tree.height()).length).toBe(0)
+    expect(tree.levelBoxes(100).length).toBe(0)
+    expect(new RBushWasm().levelBoxes(0).length).toBe(0)
+  })
+})
+
+describe("RBush Remove By Box", () => {
+  test("removeByBox removes every item with the given box", () => {
+    const tree = new RBushWasm(4)
+    for (let i = 0; i < 100; i++) {
+      tree.insert({ minX: i, minY: i, maxX: i + 1, maxY: i + 1 })
+    }
+    for (let i = 0; i < 3; i++) {
+      tree.insert({ minX: 10, minY: 10, maxX: 11, maxY: 11, copy: i })
+    }
+    tree.insert({ minX: 10, minY: 10, maxX: 11.001, maxY: 11 })
+
+    expect(tree.removeByBox(10, 10, 11, 11)).toBe(4)
+    expect(tree.len()).toBe(100)
+    expect(tree.removeByBox(10, 10, 11, 11)).toBe(0)
+    expect(tree.removeByBox(10, 10, 11, 11, 0.01)).toBe(1)
+    expect(tree.len()).toBe(99)
+    expect(tree.search({ minX: 10.2, minY: 10.2, maxX: 10.8, maxY: 10.8 })).toEqual([])
+    expect(tree.search({ minX: 50.2, minY: 50.2, maxX: 50.8, maxY: 50.8 }).length).toBe(1)
+  })
+})
+
+describe("RBush Choose Subtree Ties", () => {
+  test("identical insert sequences build identical trees", () => {
+    // Many equal-area, equal-enlargement candidates: ties fall to the
+    // lowest-area child, then the lowest index.
+    const items = []
+    for (let i = 0; i < 300; i++) {
+      const x = (i * 7) % 20
+      const y = (i * 13) % 20
+      items.push({ minX: x, minY: y, maxX: x + 1, maxY: y + 1 })
+    }
+    const a = new RBushWasm(4)
+    const b = new RBushWasm(4)
+    for (const item of items) a.insert(item)
+    for (const item of items) b.insert({ ...item })
+    expect(a.toJSON()).toEqual(b.toJSON())
+  })
+})
+
+describe("RBush Covers Point", () => {
+  test("coversPoint agrees with searchPoint", () => {
+    const tree = new RBushWasm(4)
+    for (let i = 0; i < 100; i++) {
+      const x = (i % 10) * 10
+      const y = Math.floor(i / 10) * 10
+      tree.insert({ minX: x, minY: y, maxX: x + 5, maxY: y + 5 })
+    }
+
+    expect(tree.coversPoint(2, 2)).toBe(true)
+    expect(tree.coversPoint(5, 5)).toBe(true)
+    expect(tree.coversPoint(7, 7)).toBe(false)
+    expect(tree.coversPoint(-1, 0)).toBe(false)
+    for (let i = 0; i < 200; i++) {
+      const x = Math.random() * 100
+      const y = Math.random() * 100
+      expect(tree.coversPoint(x, y)).toBe(tree.searchPoint(x, y).length > 0)
+    }
+    expect(new RBushWasm().coversPoint(0, 0)).toBe(false)
+  })
+
+  test("coversPoint and findGaps agree on padded items", () => {
+    const tree = new RBushWasm(4)
+    tree.insertPadded({ minX: 0, minY: 0, maxX: 2, maxY: 2 }, 2)
+
+    // the cell centered on (3, 1) lies in the padding only
+    expect(tree.coversPoint(1, 1)).toBe(true)
+    expect(tree.coversPoint(3, 1)).toBe(false)
+    expect(tree.findGaps({ minX: 0, minY: 0, maxX: 4, maxY: 2 }, 2)).toEqual([
+      { minX: 2, minY: 0, maxX: 4, maxY: 2 },
+    ])
+  })
+})
+
+describe("RBush Coverage Count", () => {
+  test("coverageCountAt separates gaps, clean coverage and overlaps", () => {
+    const tree = new RBushWasm(4)
+    tree.insert({ minX: 0, minY: 0, maxX: 2, maxY: 2 })
+    tree.insert({ minX: 2, minY: 0, maxX: 4, maxY: 2 })
+    tree.insert({ minX: 3, minY: 0, maxX: 5, maxY: 2 })
+
+    expect(tree.coverageCountAt(1, 1)).toBe(1)
+    expect(tree.coverageCountAt(4.5, 1)).toBe(1)
+    expect(tree.coverageCountAt(6, 1)).toBe(0)
+    expect(tree.coverageCountAt(3.5, 1)).toBe(2)
+    // a shared edge belongs to both touching items
+    expect(tree.coverageCountAt(2, 1)).toBe(2)
+    expect(new RBushWasm().coverageCountAt(0, 0)).toBe(0)
+  })
+})
+
+describe("RBush Binary Format", () => {
+  test("toBytes/fromBytes round-trips numeric trees", () => {
+    const coords = new Float64Array(4000)
+    const ids = new Float64Array(1000)
+    for (let i = 0; i < 1000; i++) {
+      const x = Math.random() * 100
+      const y = Math.random() * 100
+      coords.set([x, y, x + 1, y + 1], i * 4)
+      ids[i] = i
+    }
+    const tree = new RBushWasm(8)
+    tree.loadFlat(coords, ids)
+
+    const bytes = tree.toBytes()
+    expect(bytes).toBeInstanceOf(Uint8Array)
+    expect(String.fromCharCode(...bytes.subarray(0, 4))).toBe("RBSH")
+
+    const copy = new RBushWasm(8)
+    copy.fromBytes(bytes)
+    expect(copy.len()).toBe(1000)
+    expect(copy.allOrdered()).toEqual(tree.allOrdered())
+    expect(copy.toJSON()).toEqual(tree.toJSON())
+  })
+
+  test("fromBytes takes object data from a side channel", () => {
+    const tree = new RBushWasm(4)
+    for (let i = 0; i < 50; i++) {
+      tree.insert({ minX: i, minY: i, maxX: i + 1, maxY: i + 1, id: i })
+    }
+    const bytes = tree.toBytes()
+    const items = tree.allOrdered()
+
+    const copy = new RBushWasm(4)
+    copy.fromBytes(bytes, items)
+    expect(copy.toJSON()).toEqual(tree.toJSON())
+    expect(() => copy.fromBytes(bytes, items.slice(1))).toThrow()
+  })
+
+  test("fromBytes rejects foreign, truncated and future-version blobs", () => {
+    const tree = new RBushWasm(4)
+    tree.insert({ minX: 0, minY: 0, maxX: 1, maxY: 1 })
+    const bytes = tree.toBytes()
+
+    const copy = new RBushWasm(4)
+    expect(() => copy.fromBytes(new Uint8Array([1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]))).toThrow()
+    expect(() => copy.fromBytes(bytes.subarray(0, bytes.length - 1))).toThrow("truncated")
+    const future = bytes.slice()
+    future[4] = 99
+    expect(() => copy.fromBytes(future)).toThrow("version")
+    expect(copy.len()).toBe(0)
+  })
+})
+
+describe("RBush For Each Node", () => {
+  test("forEachNode visits every node and can prune subtrees", () => {
+    const tree = new RBushWasm(4)
+    const items = []
+    for (let i = 0; i < 100; i++) {
+      items.push({ minX: i, minY: i, maxX: i + 1, maxY: i + 1 })
+    }
+    tree.load(items)
+
+    const visits = []
+    tree.forEachNode((minX, minY, maxX, maxY, height, isLeaf, childCount) => {
+      visits.push({ minX, minY, maxX, maxY, height, isLeaf, childCount })
+    })
+    expect(visits.length).toBe(tree.nodeCount())
+    expect(visits[0]).toMatchObject({ minX: 0, minY: 0, maxX: 100, maxY: 100, height: tree.height() })
+    const leaves = visits.filter((v) => v.isLeaf)
+    expect(leaves.every((v) => v.height === 1)).toBe(true)
+    expect(leaves.reduce((sum, v) => sum + v.childCount, 0)).toBe(100)
+
+    let pruned = 0
+    tree.forEachNode(() => {
+      pruned++
+      return false
+    })
+    expect(pruned).toBe(1)
+
+    expect(() => tree.forEachNode(() => { throw new Error("boom") })).toThrow("boom")
+  })
+})
+
+describe("RBush Insert Levels", () => {
+  // Items reached through `leaf` nodes, failing if node heights skip a level.
+  function countLeafItems(node) {
+    if (node.leaf) return node.children.length
+    let count = 0
+    for (const child of node.children) {
+      expect(child.height).toBe(node.height - 1)
+      count += countLeafItems(child)
+    }
+    return count
+  }
+
+  test("insert links every item at the bottom level", () => {
+    const tree = new RBushWasm(4)
+    for (let i = 0; i < 200; i++) {
+      tree.insert({ minX: i, minY: i, maxX: i + 1, maxY: i + 1 })
+      expect(countLeafItems(tree.toJSON())).toBe(i + 1)
+    }
+    expect(tree.toJSON().height).toBeGreaterThan(2)
+  })
+
+  test("insertLevel grafts nodes at their height and keeps them searchable", () => {
+    const tree = new RBushWasm(4)
+    const items = []
+    for (let i = 0; i < 100; i++) items.push({ minX: i, minY: 0, maxX: i + 1, maxY: 1 })
+    tree.load(items)
+    const height = tree.toJSON().height
+
+    // 30 bottom-level nodes of 3 items each, above the loaded row
+    const grafted = []
+    const nodes = []
+    for (let n = 0; n < 30; n++) {
+      const children = []
+      for (let i = 0; i < 3; i++) {
+        const item = { minX: n * 3 + i, minY: 10, maxX: n * 3 + i + 1, maxY: 11 }
+        children.push(item)
+        grafted.push(item)
+      }
+      nodes.push({ children, height: 1, leaf: true, minX: 0, minY: 0, maxX: 0, maxY: 0 })
+    }
+    tree.insertLevel(nodes, height - 1)
+
+    const json = tree.toJSON()
+    expect(countLeafItems(json)).toBe(190)
+    expect(json.height).toBeGreaterThanOrEqual(height)
+    expect(tree.len()).toBe(190)
+    for (const item of grafted) expect(tree.search(item)).toContain(item)
+    expect(tree.search({ minX: 0, minY: 5, maxX: 100, maxY: 20 })).toHaveLength(90)
+    expect(() => tree.insertLevel(nodes, height + 5)).toThrow("level")
+  })
+
+  test("insertLevel rejects subtrees with mixed-depth children", () => {
+    const tree = new RBushWasm(4)
+    const items = []
+    for (let i = 0; i < 200; i++) items.push({ minX: i, minY: i, maxX: i + 1, maxY: i + 1 })
+    tree.load(items)
+    expect(tree.height()).toBeGreaterThanOrEqual(4)
+    const before = tree.toJSON()
+
+    const item = (x) => ({ minX: x, minY: 500, maxX: x + 1, maxY: 501 })
+    const leaf = (x) => ({ children: [item(x), item(x + 1)], height: 1, leaf: true, minX: 0, minY: 0, maxX: 0, maxY: 0 })
+    const node = (height, children) => ({ children, height, leaf: false, minX: 0, minY: 0, maxX: 0, maxY: 0 })
+    const level = tree.height() - 3
+
+    // one branch two levels deep, the other holding a leaf node directly
+    const mixed = node(3, [node(2, [leaf(0), leaf(2)]), leaf(4)])
+    expect(() => tree.insertLevel([mixed], level)).toThrow("inconsistent node heights")
+    // an item-holding node claiming a height above 1
+    const tall = node(3, [node(2, [{ ...leaf(0), height: 2 }])])
+    expect(() => tree.insertLevel([tall], level)).toThrow("inconsistent node heights")
+    expect(tree.toJSON()).toEqual(before)
+    expect(tree.len()).toBe(200)
+
+    tree.insertLevel([node(3, [node(2, [leaf(0), leaf(2)]), node(2, [leaf(4)])])], level)
+    expect(tree.len()).toBe(206)
+  })
+})
+
+describe("RBush Search And Filter", () => {
+  // Heights step down by one, bboxes are exactly the union of their
+  // children, and no node is overfull or (below the root) underfull.
+  const checkTree = (tree) => {
+    const walk = (node, isRoot) => {
+      expect(node.children.length).toBeLessThanOrEqual(tree.maxEntries)
+      if (!isRoot) expect(node.children.length).toBeGreaterThanOrEqual(tree.minEntries)
+      const union = { minX: Infinity, minY: Infinity, maxX: -Infinity, maxY: -Infinity }
+      for (const child of node.children) {
+        union.minX = Math.min(union.minX, child.minX)
+        union.minY = Math.min(union.minY, child.minY)
+        union.maxX = Math.max(union.maxX, child.maxX)
+        union.maxY = Math.max(union.maxY, child.maxY)
+        if (node.leaf) continue
+        expect(child.height).toBe(node.height - 1)
+        walk(child, false)
+      }
+      if (node.children.length > 0) {
+        expect([node.minX, node.minY, node.maxX, node.maxY]).toEqual([union.minX, union.minY, union.maxX, union.maxY])
+      }
+    }
+    walk(tree.toJSON(), true)
+  }
+  const sortedIds = (results) => results.map((item) => item.id).sort((a, b) => a - b)
+
+  test("the tree stays valid after a bulk removal", () => {
+    const tree = new RBushWasm(4)
+    const items = []
+    for (let i = 0; i < 400; i++) {
+      const x = Math.random() * 100
+      const y = Math.random() * 100
+      const item = { minX: x, minY: y, maxX: x + 1, maxY: y + 1, id: i }
+      items.push(item)
+      tree.insert(item)
+    }
+    checkTree(tree)
+    const height = tree.height()
+
+    // drop the odd ids in the left half, then everything in a central band
+    const removed = tree.searchAndFilter({ minX: 0, minY: 0, maxX: 50, maxY: 101 }, (item) => item.id % 2 === 0)
+    const odd = items.filter((item) => item.minX <= 50 && item.id % 2 === 1)
+    expect(removed).toBe(odd.length)
+    checkTree(tree)
+
+    const band = { minX: 0, minY: 30, maxX: 101, maxY: 70 }
+    const inBand = (item) => item.minY <= band.maxY && item.maxY >= band.minY
+    const left = items.filter((item) => !odd.includes(item))
+    expect(tree.searchAndFilter(band, () => false)).toBe(left.filter(inBand).length)
+    checkTree(tree)
+    expect(tree.height()).toBeLessThanOrEqual(height)
+
+    const remaining = left.filter((item) => !inBand(item))
+    expect(tree.len()).toBe(remaining.length)
+    expect(sortedIds(tree.all())).toEqual(sortedIds(remaining))
+  })
+})
+
+describe("RBush Pins", () => {
+  test("every removal path frees the pins it takes out", () => {
+    const tree = new RBushWasm(4)
+    const items = []
+    const pins = []
+    for (let i = 0; i < 50; i++) {
+      const item = { minX: i, minY: 0, maxX: i + 0.5, maxY: 1, id: i }
+      items.push(item)
+      pins.push(tree.insertPinned(item))
+    }
+    expect(tree.pinCount()).toBe(50)
+    expect(tree.searchPin(pins[7])).toBe(items[7])
+
+    tree.remove(items[0])
+    expect(tree.removeAll([items[1], items[2]])).toBe(2)
+    tree.removeWith({ id: 3 }, (a, b) => a.id === b.id)
+    expect(tree.searchAndFilter({ minX: 4, minY: 0, maxX: 5, maxY: 1 }, () => false)).toBe(2)
+    expect(tree.removeByBox(6, 0, 6.5, 1)).toBe(1)
+    expect(tree.transferRegion(new RBushWasm(), { minX: 7, minY: 0, maxX: 8, maxY: 1 })).toBe(1)
+    expect(tree.pinCount()).toBe(42)
+    for (let i = 0; i < 8; i++) expect(tree.searchPin(pins[i])).toBe(null)
+
+    tree.optimize()
+    expect(tree.searchPin(pins[20])).toBe(items[20])
+    tree.clear()
+    expect(tree.pinCount()).toBe(0)
+  })
+})
+
+describe("RBush Structurally Equals", () => {
+  const items = []
+  for (let i = 0; i < 60; i++) {
+    items.push({ minX: i, minY: i % 6, maxX: i + 1, maxY: (i % 6) + 1, id: i })
+  }
+
+  test("trees built the same way with the same items are equal", () => {
+    const a = new RBushWasm(4)
+    const b = new RBushWasm(4)
+    a.load(items)
+    b.load(items)
+    expect(a.structurallyEquals(b)).toBe(true)
+    expect(b.structurallyEquals(a)).toBe(true)
+    expect(a.structurallyEquals(a.clone())).toBe(true)
+    expect(new RBushWasm().structurallyEquals(new RBushWasm(16))).toBe(true)
+  })
+
+  test("differing items, bboxes or shape make trees unequal", () => {
+    const a = new RBushWasm(4)
+    a.load(items)
+
+    // same boxes, different objects: items compare by reference
+    const copies = new RBushWasm(4)
+    copies.load(items.map((item) => ({ ...item })))
+    expect(a.structurallyEquals(copies)).toBe(false)
+
+    const grown = a.clone()
+    grown.insert({ minX: 0, minY: 0, maxX: 1, maxY: 1, id: "extra" })
+    expect(a.structurallyEquals(grown)).toBe(false)
+
+    // same items inserted one by one end up in a different layout
+    const inserted = new RBushWasm(4)
+    for (const item of items) inserted.insert(item)
+    expect(inserted.all().length).toBe(a.all().length)
+    expect(a.structurallyEquals(inserted)).toBe(false)
+  })
+})
+
+describe("RBush Search By Leaf", () => {
+  test("groups the matches by the leaf node holding them", () => {
+    const tree = new RBushWasm(4)
+    const items = []
+    for (let i = 0; i < 200; i++) {
+      const x = Math.random() * 100
+      const y = Math.random() * 100
+      items.push({ minX: x, minY: y, maxX: x + 1, maxY: y + 1, id: i })
+    }
+    tree.load(items)
+
+    const box = { minX: 20, minY: 20, maxX: 70, maxY: 60 }
+    const groups = tree.searchByLeaf(box)
+    const ids = (list) => list.map((item) => item.id).sort((a, b) => a - b)
+    expect(ids(groups.flat())).toEqual(ids(tree.search(box)))
+    expect(groups.every((group) => group.length > 0 && group.length <= tree.maxEntries)).toBe(true)
+
+    // each group is a subset of one of the tree's leaf clusters
+    const clusterOf = new Map()
+    tree.leafClusters().forEach((cluster, i) => cluster.items.forEach((item) => clusterOf.set(item, i)))
+    for (const group of groups) {
+      expect(new Set(group.map((item) => clusterOf.get(item))).size).toBe(1)
+    }
+
+    expect(tree.searchByLeaf({ minX: 200, minY: 200, maxX: 300, maxY: 300 })).toEqual([])
+  })
+})
+
+describe("RBush Centroid", () => {
+  const tree = new RBushWasm(4)
+  tree.load([
+    { minX: 0, minY: 0, maxX: 2, maxY: 2 }, // center (1, 1), area 4
+    { minX: 4, minY: 0, maxX: 6, maxY: 2 }, // center (5, 1), area 4
+    { minX: 0, minY: 6, maxX: 4, maxY: 10 }, // center (2, 8), area 16
+    { minX: 50, minY: 50, maxX: 51, maxY: 51 },
+  ])
+  const box = { minX: 0, minY: 0, maxX: 10, maxY: 10 }
+
+  test("averages the matching bbox centers", () => {
+    expect(tree.centroid(box)).toEqual({ x: 8 / 3, y: 10 / 3 })
+    expect(tree.centroid(box, false)).toEqual({ x: 8 / 3, y: 10 / 3 })
+    expect(tree.centroid({ minX: 100, minY: 100, maxX: 200, maxY: 200 })).toBe(null)
+  })
+
+  test("weighted weighs each center by its bbox area", () => {
+    const { x, y } = tree.centroid(box, true)
+    expect(x).toBeCloseTo((1 * 4 + 5 * 4 + 2 * 16) / 24)
+    expect(y).toBeCloseTo((1 * 4 + 1 * 4 + 8 * 16) / 24)
+  })
+
+  test("weighted falls back to the plain average when every match has zero area", () => {
+    const points = new RBushWasm(4)
+    points.load([
+      { minX: 0, minY: 0, maxX: 0, maxY: 0 },
+      { minX: 4, minY: 2, maxX: 4, maxY: 2 },
+    ])
+    expect(points.centroid(box, true)).toEqual({ x: 2, y: 1 })
+  })
+})
+
+describe("RBush Search Thinned", () => {
+  const tree = new RBushWasm(4)
+  const items = []
+  for (let i = 0; i < 400; i++) {
+    const x = Math.random() * 100
+    const y = Math.random() * 100
+    items.push({ minX: x, minY: y, maxX: x + 0.5, maxY: y + 0.5, id: i })
+  }
+  tree.load(items)
+  const box = { minX: 10, minY: 10, maxX: 90, maxY: 90 }
+  const center = (item) => [(item.minX + item.maxX) / 2, (item.minY + item.maxY) / 2]
+
+  test("no two kept centers are closer than the separation", () => {
+    const kept = tree.searchThinned(box, 8)
+    const matches = tree.search(box)
+    expect(kept.length).toBeGreaterThan(0)
+    expect(kept.length).toBeLessThan(matches.length)
+    expect(kept.every((item) => matches.includes(item))).toBe(true)
+    for (let i = 0; i < kept.length; i++) {
+      for (let j = i + 1; j < kept.length; j++) {
+        const [ax, ay] = center(kept[i])
+        const [bx, by] = center(kept[j])
+        expect(Math.hypot(ax - bx, ay - by)).toBeGreaterThanOrEqual(8)
+      }
+    }
+
+    // greedy: every dropped match is near some kept one
+    for (const item of matches.filter((item) => !kept.includes(item))) {
+      const [x, y] = center(item)
+      expect(kept.some((other) => Math.hypot(center(other)[0] - x, center(other)[1] - y) < 8)).toBe(true)
+    }
+  })
+
+  test("a non-positive separation keeps every match", () => {
+    const ids = (list) => list.map((item) => item.id).sort((a, b) => a - b)
+    expect(ids(tree.searchThinned(box, 0))).toEqual(ids(tree.search(box)))
+    expect(ids(tree.searchThinned(box, -1))).toEqual(ids(tree.search(box)))
+  })
+})
+
+describe("RBush Search By Aspect", () => {
+  const wide = { minX: 0, minY: 0, maxX: 4, maxY: 1, id: "wide" } // 4
+  const square = { minX: 5, minY: 0, maxX: 7, maxY: 2, id: "square" } // 1
+  const tall = { minX: 8, minY: 0, maxX: 9, maxY: 3, id: "tall" } // 1/3
+  const flat = { minX: 0, minY: 5, maxX: 3, maxY: 5, id: "flat" } // infinite
+  const point = { minX: 5, minY: 5, maxX: 5, maxY: 5, id: "point" } // none
+  const tree = new RBushWasm(4)
+  tree.load([wide, square, tall, flat, point])
+  const box = { minX: 0, minY: 0, maxX: 10, maxY: 10 }
+  const ids = (list) => list.map((item) => item.id).sort()
+
+  test("keeps matches whose width / height is within the range, inclusive", () => {
+    expect(ids(tree.searchByAspect(box, 1, 4))).toEqual(["square", "wide"])
+    expect(ids(tree.searchByAspect(box, 0, 1))).toEqual(["square", "tall"])
+    expect(ids(tree.searchByAspect(box, 1.5, 3.5))).toEqual([])
+    expect(ids(tree.searchByAspect({ minX: 0, minY: 0, maxX: 4, maxY: 4 }, 0, 100))).toEqual(["wide"])
+  })
+
+  test("zero-height boxes have an infinite ratio and zero-size boxes never match", () => {
+    expect(ids(tree.searchByAspect(box, 10, Infinity))).toEqual(["flat"])
+    expect(ids(tree.searchByAspect(box, 0, Infinity))).toEqual(["flat", "square", "tall", "wide"])
+  })
+})
+
+describe("RBush Find Gaps", () => {
+  const box = { minX: 0, minY: 0, maxX: 10, maxY: 10 }
+
+  test("merges uncovered grid cells into rectangles", () => {
+    const tree = new RBushWasm(4)
+    tree.insert({ minX: 0, minY: 0, maxX: 10, maxY: 4 })
+    tree.insert({ minX: 0, minY: 4, maxX: 5, maxY: 10 })
+    expect(tree.findGaps(box, 10)).toEqual([{ minX: 5, minY: 4, maxX: 10, maxY: 10 }])
+
+    // a hole in the middle splits the rows around it into separate runs
+    tree.clear()
+    tree.insert({ minX: 0, minY: 0, maxX: 10, maxY: 10 })
+    expect(tree.findGaps(box, 10)).toEqual([])
+    const holed = new RBushWasm(4)
+    holed.load([
+      { minX: 0, minY: 0, maxX: 10, maxY: 3 },
+      { minX: 0, minY: 7, maxX: 10, maxY: 10 },
+      { minX: 0, minY: 3, maxX: 3, maxY: 7 },
+      { minX: 7, minY: 3, maxX: 10, maxY: 7 },
+    ])
+    expect(holed.findGaps(box, 10)).toEqual([{ minX: 3, minY: 3, maxX: 7, maxY: 7 }])
+  })
+
+  test("accuracy follows the grid: gaps between sample points are missed", () => {
+    const tree = new RBushWasm(4)
+    // covers every cell center of a 2 x 2 grid but leaves most of the box open
+    for (const x of [2.5, 7.5]) {
+      for (const y of [2.5, 7.5]) tree.insert({ minX: x, minY: y, maxX: x, maxY: y })
+    }
+    expect(tree.findGaps(box, 2)).toEqual([])
+    expect(tree.findGaps(box, 10).length).toBeGreaterThan(0)
+  })
+
+  test("an empty tree is one gap, and a zero grid finds none", () => {
+    const tree = new RBushWasm()
+    expect(tree.findGaps(box, 4)).toEqual([box])
+    expect(tree.findGaps(box, 0)).toEqual([])
+  })
+})
+
+describe("RBush Knn Box", () => {
+  const tree = new RBushWasm(4)
+  const items = []
+  for (let i = 0; i < 300; i++) {
+    const x = Math.random() * 100
+    const y = Math.random() * 100
+    items.push({ minX: x, minY: y, maxX: x + Math.random() * 3, maxY: y + Math.random() * 3, id: i })
+  }
+  tree.load(items)
+  const gap = (a, b) => {
+    const dx = Math.max(0, a.minX - b.maxX, b.minX - a.maxX)
+    const dy = Math.max(0, a.minY - b.maxY, b.minY - a.maxY)
+    return Math.sqrt(dx * dx + dy * dy)
+  }
+
+  test("returns the k items with the smallest box-to-box gap, nearest first", () => {
+    const box = { minX: 40, minY: 40, maxX: 45, maxY: 60 }
+    const result = tree.knnBox(box, 15)
+    expect(result.length).toBe(15)
+    const dists = result.map((item) => gap(box, item))
+    expect(dists).toEqual([...dists].sort((a, b) => a - b))
+    const cutoff = dists[dists.length - 1]
+    const closer = items.filter((item) => gap(box, item) < cutoff)
+    expect(closer.every((item) => result.includes(item))).toBe(true)
+  })
+
+  test("intersecting items are at distance 0 and come first", () => {
+    const box = { minX: 10, minY: 10, maxX: 30, maxY: 30 }
+    const hits = tree.search(box)
+    const result = tree.knnBox(box, hits.length + 3)
+    expect(result.slice(0, hits.length).every((item) => hits.includes(item))).toBe(true)
+    expect(result.slice(hits.length).every((item) => gap(box, item) > 0)).toBe(true)
+    expect(tree.knnBox(box, 0)).toEqual([])
+    expect(tree.knnBox(box, 1000).length).toBe(300)
+  })
+})
+
+describe("RBush Leaf Clusters", () => {
+  test("returns every bottom-level node with its bbox and items", () => {
+    const tree = new RBushWasm(4)
+    const items = []
+    for (let i = 0; i < 150; i++) {
+      const x = Math.random() * 100
+      const y = Math.random() * 100
+      items.push({ minX: x, minY: y, maxX: x + 2, maxY: y + 2, id: i })
+    }
+    tree.load(items)
+
+    const clusters = tree.leafClusters()
+    expect(clusters.length).toBe(tree.levelSizes().nodesPerLevel[tree.height() - 1])
+    const seen = clusters.flatMap((cluster) => cluster.items)
+    expect(seen.length).toBe(150)
+    expect(new Set(seen).size).toBe(150)
+
+    for (const cluster of clusters) {
+      expect(cluster.items.length).toBeLessThanOrEqual(tree.maxEntries)
+      expect(cluster.minX).toBe(Math.min(...cluster.items.map((item) => item.minX)))
+      expect(cluster.minY).toBe(Math.min(...cluster.items.map((item) => item.minY)))
+      expect(cluster.maxX).toBe(Math.max(...cluster.items.map((item) => item.maxX)))
+      expect(cluster.maxY).toBe(Math.max(...cluster.items.map((item) => item.maxY)))
+    }
+  })
+
+  test("a single-node tree is one cluster, and an empty tree none", () => {
+    const tree = new RBushWasm(9)
+    const a = { minX: 0, minY: 0, maxX: 1, maxY: 1 }
+    const b = { minX: 3, minY: 2, maxX: 4, maxY: 5 }
+    tree.insert(a)
+    tree.insert(b)
+    const clusters = tree.leafClusters()
+    expect(clusters.length).toBe(1)
+    expect(clusters[0]).toMatchObject({ minX: 0, minY: 0, maxX: 4, maxY: 5 })
+    expect(clusters[0].items).toEqual([a, b])
+    expect(new RBushWasm().leafClusters()).toEqual([])
+  })
+})
+
+describe("RBush Search Edge Sorted", () => {
+  test("sorts matches by the distance from their center to the nearest query edge", () => {
+    const tree = new RBushWasm()
+    const middle = { minX: 45, minY: 45, maxX: 55, maxY: 55 }
+    const nearLeft = { minX: 9, minY: 49, maxX: 11, maxY: 51 }
+    const nearTop = { minX: 40, minY: 94, maxX: 44, maxY: 98 }
+    const outside = { minX: -6, minY: 20, maxX: 2, maxY: 22 }
+    const elsewhere = { minX: 200, minY: 200, maxX: 210, maxY: 210 }
+    tree.load([middle, nearLeft, nearTop, outside, elsewhere])
+
+    // Edge distances: outside -2, nearTop 4, nearLeft 10, middle 50.
+    const result = tree.searchEdgeSorted({ minX: 0, minY: 0, maxX: 100, maxY: 100 })
+    expect(result).toEqual([outside, nearTop, nearLeft, middle])
+  })
+
+  test("returns the same items as search", () => {
+    const tree = new RBushWasm(4)
+    const items = []
+    for (let i = 0; i < 100; i++) {
+      const x = Math.random() * 100
+      const y = Math.random() * 100
+      items.push({ minX: x, minY: y, maxX: x + 3, maxY: y + 3, id: i })
+    }
+    tree.load(items)
+    const bbox = { minX: 20, minY: 20, maxX: 70, maxY: 60 }
+    const edge = (item) => {
+      const cx = (item.minX + item.maxX) / 2
+      const cy = (item.minY + item.maxY) / 2
+      return Math.min(cx - bbox.minX, bbox.maxX - cx, cy - bbox.minY, bbox.maxY - cy)
+    }
+
+    const result = tree.searchEdgeSorted(bbox)
+    expect(new Set(result)).toEqual(new Set(tree.search(bbox)))
+    for (let i = 1; i < result.length; i++) {
+      expect(edge(result[i - 1])).toBeLessThanOrEqual(edge(result[i]))
+    }
+    expect(tree.searchEdgeSorted({ minX: 500, minY: 500, maxX: 600, maxY: 600 })).toEqual([])
+  })
+})
+
+describe("RBush Search Hull", () => {
+  test("returns the counter-clockwise hull of the matching corners", () => {
+    const tree = new RBushWasm()
+    tree.load([
+      { minX: 0, minY: 0, maxX: 2, maxY: 2 },
+      { minX: 1, minY: 1, maxX: 3, maxY: 3 },
+      { minX: 8, minY: 0, maxX: 10, maxY: 1 },
+      { minX: 4, minY: 6, maxX: 5, maxY: 8 },
+      { minX: 50, minY: 50, maxX: 60, maxY: 60 },
+    ])
+
+    const hull = tree.searchHull({ minX: 0, minY: 0, maxX: 10, maxY: 10 })
+    expect(hull).toEqual([0, 0, 10, 0, 10, 1, 5, 8, 4, 8, 0, 2])
+  })
+
+  test("encloses every matching corner in a convex ring", () => {
+    const tree = new RBushWasm(4)
+    const items = []
+    for (let i = 0; i < 60; i++) {
+      const x = Math.random() * 100
+      const y = Math.random() * 100
+      items.push({ minX: x, minY: y, maxX: x + Math.random() * 5, maxY: y + Math.random() * 5 })
+    }
+    tree.load(items)
+    const bbox = { minX: 10, minY: 10, maxX: 80, maxY: 80 }
+
+    const flat = tree.searchHull(bbox)
+    const ring = []
+    for (let i = 0; i < flat.length; i += 2) ring.push([flat[i], flat[i + 1]])
+    const cross = (o, a, b) => (a[0] - o[0]) * (b[1] - o[1]) - (a[1] - o[1]) * (b[0] - o[0])
+    const corners = tree
+      .search(bbox)
+      .flatMap((b) => [[b.minX, b.minY], [b.maxX, b.minY], [b.maxX, b.maxY], [b.minX, b.maxY]])
+
+    expect(ring.length).toBeGreaterThanOrEqual(3)
+    for (let i = 0; i < ring.length; i++) {
+      const a = ring[i]
+      const b = ring[(i + 1) % ring.length]
+      expect(cross(a, b, ring[(i + 2) % ring.length])).toBeGreaterThan(0)
+      for (const p of corners) expect(cross(a, b, p)).toBeGreaterThanOrEqual(-1e-9)
+    }
+  })
+
+  test("returns fewer than three distinct points as-is", () => {
+    const tree = new RBushWasm()
+    tree.insert({ minX: 3, minY: 4, maxX: 3, maxY: 4 })
+    tree.insert({ minX: 3, minY: 4, maxX: 3, maxY: 4 })
+    expect(tree.searchHull({ minX: 0, minY: 0, maxX: 10, maxY: 10 })).toEqual([3, 4])
+    expect(tree.searchHull({ minX: 20, minY: 20, maxX: 30, maxY: 30 })).toEqual([])
+  })
+})
+
+describe("RBush Collides Sampled", () => {
+  test("agrees with collides when the budget is large enough", () => {
+    const tree = new RBushWasm(4)
+    const items = []
+    for (let i = 0; i < 200; i++) {
+      const x = Math.random() * 1000
+      const y = Math.random() * 1000
+      items.push({ minX: x, minY: y, maxX: x + 5, maxY: y + 5 })
+    }
+    tree.load(items)
+
+    for (let i = 0; i < 50; i++) {
+      const x = Math.random() * 1000
+      const y = Math.random() * 1000
+      const bbox = { minX: x, minY: y, maxX: x + 10, maxY: y + 10 }
+      expect(tree.collidesSampled(bbox, 1e6)).toBe(tree.collides(bbox))
+    }
+  })
+
+  test("returns \"unknown\" when it runs out of checks", () => {
+    const tree = new RBushWasm(4)
+    const items = []
+    for (let i = 0; i < 100; i++) {
+      items.push({ minX: i * 10, minY: 0, maxX: i * 10 + 1, maxY: 1 })
+    }
+    tree.load(items)
+    expect(tree.height()).toBeGreaterThan(1)
+
+    const inside = { minX: 500.25, minY: 0.25, maxX: 500.5, maxY: 0.5 }
+    expect(tree.collidesSampled(inside, 0)).toBe("unknown")
+    expect(tree.collidesSampled(inside, 1)).toBe("unknown")
+    expect(tree.collidesSampled(inside, 1e6)).toBe(true)
+  })
+
+  test("settles a single-node tree with one check", () => {
+    const tree = new RBushWasm()
+    tree.load([
+      { minX: 0, minY: 0, maxX: 1, maxY: 1 },
+      { minX: 5, minY: 5, maxX: 6, maxY: 6 },
+    ])
+    expect(tree.collidesSampled({ minX: 0.5, minY: 0.5, maxX: 2, maxY: 2 }, 1)).toBe(true)
+    expect(tree.collidesSampled({ minX: 2, minY: 2, maxX: 3, maxY: 3 }, 1)).toBe(false)
+  })
+})
+
+describe("RBush Search Partition", () => {
+  test("splits the leaf nodes with a match into inside and nearby items", () => {
+    const tree = new RBushWasm(4)
+    const items = []
+    for (let i = 0; i < 120; i++) {
+      const x = Math.random() * 100
+      const y = Math.random() * 100
+      items.push({ minX: x, minY: y, maxX: x + 2, maxY: y + 2, id: i })
+    }
+    tree.load(items)
+    const bbox = { minX: 30, minY: 30, maxX: 60, maxY: 50 }
+
+    const { inside, nearby } = tree.searchPartition(bbox)
+    const hits = new Set(tree.search(bbox))
+    expect(new Set(inside)).toEqual(hits)
+
+    const expectedNearby = new Set()
+    for (const cluster of tree.leafClusters()) {
+      if (cluster.items.some((item) => hits.has(item))) {
+        for (const item of cluster.items) if (!hits.has(item)) expectedNearby.add(item)
+      }
+    }
+    expect(nearby.length).toBe(expectedNearby.size)
+    expect(new Set(nearby)).toEqual(expectedNearby)
+  })
+
+  test("returns two empty arrays when nothing matches", () => {
+    const tree = new RBushWasm()
+    tree.load([
+      { minX: 0, minY: 0, maxX: 1, maxY: 1 },
+      { minX: 2, minY: 2, maxX: 3, maxY: 3 },
+    ])
+    expect(tree.searchPartition({ minX: 10, minY: 10, maxX: 20, maxY: 20 })).toEqual({
+      inside: [],
+      nearby: [],
+    })
+    const near = tree.searchPartition({ minX: 0, minY: 0, maxX: 0.5, maxY: 0.5 })
+    expect(near.inside.length).toBe(1)
+    expect(near.nearby).toEqual([{ minX: 2, minY: 2, maxX: 3, maxY: 3 }])
+  })
+})
+
+describe("RBush Find Hotspots", () => {
+  test("reports leaf clusters with more than maxOverlap overlapping items", () => {
+    const tree = new RBushWasm(4)
+    const items = []
+    for (let i = 0; i < 150; i++) {
+      const x = Math.random() * 100
+      const y = Math.random() * 100
+      const size = Math.random() * 8
+      items.push({ minX: x, minY: y, maxX: x + size, maxY: y + size })
+    }
+    tree.load(items)
+    const intersects = (a, b) =>
+      a.minX <= b.maxX && a.minY <= b.maxY && a.maxX >= b.minX && a.maxY >= b.minY
+
+    for (const maxOverlap of [0, 1, 2]) {
+      const expected = []
+      for (const cluster of tree.leafClusters()) {
+        const count = cluster.items.filter((item) =>
+          cluster.items.some((other) => other !== item && intersects(item, other))
+        ).length
+        if (count > maxOverlap) {
+          const { minX, minY, maxX, maxY } = cluster
+          expected.push({ minX, minY, maxX, maxY, count })
+        }
+      }
+      const key = (h) => [h.minX, h.minY, h.maxX, h.maxY].join()
+      const hotspots = tree.findHotspots(maxOverlap)
+      expect(hotspots.sort((a, b) => key(a).localeCompare(key(b)))).toEqual(
+        expected.sort((a, b) => key(a).localeCompare(key(b)))
+      )
+    }
+  })
+
+  test("counts only the items that overlap another one", () => {
+    const tree = new RBushWasm()
+    tree.load([
+      { minX: 0, minY: 0, maxX: 2, maxY: 2 },
+      { minX: 1, minY: 1, maxX: 3, maxY: 3 },
+      { minX: 2, minY: 2, maxX: 4, maxY: 4 },
+      { minX: 10, minY: 10, maxX: 11, maxY: 11 },
+    ])
+    expect(tree.findHotspots(2)).toEqual([{ minX: 0, minY: 0, maxX: 11, maxY: 11, count: 3 }])
+    expect(tree.findHotspots(3)).toEqual([])
+    expect(new RBushWasm().findHotspots(0)).toEqual([])
+  })
+})
+
+describe("RBush Op Stats", () => {
+  test("counts inserts, splits and removals", () => {
+    const tree = new RBushWasm(4)
+    expect(tree.opStats()).toEqual({ inserts: 0, splits: 0, rootSplits: 0, removals: 0, reinserts: 0 })
+
+    const items = []
+    for (let i = 0; i < 100; i++) {
+      const item = { minX: i, minY: i, maxX: i + 1, maxY: i + 1 }
+      items.push(item)
+      tree.insert(item)
+    }
+    let stats = tree.opStats()
+    expect(stats.inserts).toBe(100)
+    expect(stats.rootSplits).toBe(tree.height() - 1)
+    expect(stats.splits).toBeGreaterThanOrEqual(stats.rootSplits)
+    expect(stats.removals).toBe(0)
+
+    for (const item of items.slice(0, 10)) tree.remove(item)
+    tree.remove({ minX: 0, minY: 0, maxX: 1, maxY: 1 })
+    stats = tree.opStats()
+    expect(stats.removals).toBe(10)
+    expect(stats.inserts).toBe(100)
+
+    tree.load([
+      { minX: 5, minY: 5, maxX: 6, maxY: 6 },
+      { minX: 7, minY: 7, maxX: 8, maxY: 8 },
+    ])
+    expect(tree.opStats().inserts).toBe(102)
+  })
+
+  test("resets on clear and resetOpStats", () => {
+    const tree = new RBushWasm(4)
+    for (let i = 0; i < 20; i++) tree.insert({ minX: i, minY: 0, maxX: i, maxY: 0 })
+    const zero = { inserts: 0, splits: 0, rootSplits: 0, removals: 0, reinserts: 0 }
+    expect(tree.opStats()).not.toEqual(zero)
+
+    tree.resetOpStats()
+    expect(tree.opStats()).toEqual(zero)
+    expect(tree.len()).toBe(20)
+
+    tree.insert({ minX: 50, minY: 0, maxX: 50, maxY: 0 })
+    expect(tree.opStats().inserts).toBe(1)
+    tree.clear()
+    expect(tree.opStats()).toEqual(zero)
+  })
+})
+
+describe("RBush Search Obb", () => {
+  test("with no rotation, matches an axis-aligned search", () => {
+    const tree = new RBushWasm(4)
+    const items = []
+    for (let i = 0; i < 100; i++) {
+      const x = Math.random() * 100
+      const y = Math.random() * 100
+      items.push({ minX: x, minY: y, maxX: x + 2, maxY: y + 2 })
+    }
+    tree.load(items)
+    const result = tree.searchObb(50, 40, 20, 10, 0)
+    expect(new Set(result)).toEqual(new Set(tree.search({ minX: 30, minY: 30, maxX: 70, maxY: 50 })))
+  })
+
+  test("drops items inside the rotated rectangle's bounds but outside the rectangle", () => {
+    const tree = new RBushWasm()
+    const center = { minX: -0.1, minY: -0.1, maxX: 0.1, maxY: 0.1 }
+    const corner = { minX: 1.1, minY: 1.1, maxX: 1.2, maxY: 1.2 }
+    const tip = { minX: 1.3, minY: -0.05, maxX: 1.35, maxY: 0.05 }
+    const far = { minX: 5, minY: 5, maxX: 6, maxY: 6 }
+    tree.load([center, corner, tip, far])
+
+    // A unit square turned 45 degrees: a diamond reaching sqrt(2) along each axis.
+    const result = tree.searchObb(0, 0, 1, 1, Math.PI / 4)
+    expect(new Set(result)).toEqual(new Set([center, tip]))
+  })
+
+  test("rotates counterclockwise", () => {
+    const tree = new RBushWasm()
+    const above = { minX: -0.1, minY: 2.4, maxX: 0.1, maxY: 2.6 }
+    const right = { minX: 2.4, minY: -0.1, maxX: 2.6, maxY: 0.1 }
+    tree.load([above, right])
+    expect(tree.searchObb(0, 0, 3, 1, 0)).toEqual([right])
+    expect(tree.searchObb(0, 0, 3, 1, Math.PI / 2)).toEqual([above])
+    expect(tree.searchObb(10, 10, 1, 1, 0.3)).toEqual([])
+  })
+})
+
+describe("RBush Root Children Summary", () => {
+  test("summarizes each child node of the root", () => {
+    const tree = new RBushWasm(4)
+    const items = []
+    for (let i = 0; i < 200; i++) {
+      const x = Math.random() * 100
+      const y = Math.random() * 100
+      items.push({ minX: x, minY: y, maxX: x + 1, maxY: y + 1 })
+    }
+    tree.load(items)
+
+    const summary = tree.rootChildrenSummary()
+    const root = tree.toJSON()
+    expect(summary.length).toBe(root.children.length)
+    summary.forEach((child, i) => {
+      const node = root.children[i]
+      expect(child).toEqual({ minX: node.minX, minY: node.minY, maxX: node.maxX, maxY: node.maxY, count: child.count })
+      const inside = tree.search(child).filter(
+        (item) => item.minX >= child.minX && item.minY >= child.minY && item.maxX <= child.maxX && item.maxY <= child.maxY
+      )
+      expect(child.count).toBeLessThanOrEqual(inside.length)
+    })
+    expect(summary.reduce((sum, child) => sum + child.count, 0)).toBe(200)
+  })
+
+  test("lists items with a count of 1 when the root holds them directly", () => {
+    const tree = new RBushWasm()
+    tree.load([
+      { minX: 0, minY: 0, maxX: 1, maxY: 1 },
+      { minX: 2, minY: 3, maxX: 4, maxY: 5 },
+    ])
+    expect(tree.rootChildrenSummary()).toEqual([
+      { minX: 0, minY: 0, maxX: 1, maxY: 1, count: 1 },
+      { minX: 2, minY: 3, maxX: 4, maxY: 5, count: 1 },
+    ])
+    expect(new RBushWasm().rootChildrenSummary()).toEqual([])
+  })
+})
+
+describe("RBush Search Budgeted", () => {
+  test("returns the full search result when the budget suffices", () => {
+    const tree = new RBushWasm(4)
+    const items = []
+    for (let i = 0; i < 200; i++) {
+      const x = Math.random() * 100
+      const y = Math.random() * 100
+      items.push({ minX: x, minY: y, maxX: x + 2, maxY: y + 2 })
+    }
+    tree.load(items)
+    const bbox = { minX: 20, minY: 20, maxX: 60, maxY: 60 }
+
+    const { results, complete } = tree.searchBudgeted(bbox, 1e6)
+    expect(complete).toBe(true)
+    expect(new Set(results)).toEqual(new Set(tree.search(bbox)))
+  })
+
+  test("stops early with a subset when the budget runs out", () => {
+    const tree = new RBushWasm(4)
+    const items = []
+    for (let i = 0; i < 200; i++) {
+      items.push({ minX: i, minY: 0, maxX: i + 0.5, maxY: 1 })
+    }
+    tree.load(items)
+    const bbox = { minX: 0, minY: 0, maxX: 200, maxY: 1 }
+    const all = new Set(tree.search(bbox))
+
+    let previous = -1
+    for (const maxNodes of [0, 1, 2, 5, 10]) {
+      const { results, complete } = tree.searchBudgeted(bbox, maxNodes)
+      expect(complete).toBe(false)
+      for (const item of results) expect(all.has(item)).toBe(true)
+      expect(results.length).toBeGreaterThanOrEqual(previous)
+      previous = results.length
+    }
+    expect(tree.searchBudgeted(bbox, 0).results).toEqual([])
+  })
+
+  test("is complete on an empty tree whatever the budget", () => {
+    const tree = new RBushWasm()
+    const bbox = { minX: 0, minY: 0, maxX: 1, maxY: 1 }
+    expect(tree.searchBudgeted(bbox, 0)).toEqual({ results: [], complete: true })
+    expect(tree.searchBudgeted(bbox, 1)).toEqual({ results: [], complete: true })
+  })
+
+  test("is complete when the budget exactly covers the intersecting nodes", () => {
+    const tree = new RBushWasm(4)
+    const items = []
+    for (let i = 0; i < 200; i++) {
+      const x = Math.random() * 100
+      const y = Math.random() * 100
+      items.push({ minX: x, minY: y, maxX: x + 2, maxY: y + 2 })
+    }
+    tree.load(items)
+    const bbox = { minX: 20, minY: 20, maxX: 50, maxY: 40 }
+
+    let intersecting = 0
+    tree.forEachNode((minX, minY, maxX, maxY) => {
+      if (minX > bbox.maxX || minY > bbox.maxY || maxX < bbox.minX || maxY < bbox.minY) return false
+      intersecting++
+    })
+
+    const exact = tree.searchBudgeted(bbox, intersecting)
+    expect(exact.complete).toBe(true)
+    expect(new Set(exact.results)).toEqual(new Set(tree.search(bbox)))
+    expect(tree.searchBudgeted(bbox, intersecting - 1).complete).toBe(false)
+
+    // nothing intersects, so no budget is needed
+    expect(tree.searchBudgeted({ minX: 500, minY: 500, maxX: 600, maxY: 600 }, 0)).toEqual({
+      results: [],
+      complete: true,
+    })
+  })
+})
+
+describe("RBush Tightest Containing Node", () => {
+  test("finds the smallest node whose bbox contains the query", () => {
+    const tree = new RBushWasm(4)
+    const items = []
+    for (let i = 0; i < 300; i++) {
+      const x = Math.random() * 100
+      const y = Math.random() * 100
+      items.push({ minX: x, minY: y, maxX: x + 1, maxY: y + 1 })
+    }
+    tree.load(items)
+
+    for (let i = 0; i < 20; i++) {
+      const x = Math.random() * 95
+      const y = Math.random() * 95
+      const bbox = { minX: x, minY: y, maxX: x + 2, maxY: y + 2 }
+
+      let smallest = Infinity
+      tree.forEachNode((minX, minY, maxX, maxY) => {
+        if (minX <= bbox.minX && minY <= bbox.minY && bbox.maxX <= maxX && bbox.maxY <= maxY) {
+          smallest = Math.min(smallest, (maxX - minX) * (maxY - minY))
+        }
+      })
+
+      const node = tree.tightestContainingNode(bbox)
+      expect(node).not.toBeNull()
+      expect(node.minX).toBeLessThanOrEqual(bbox.minX)
+      expect(node.minY).toBeLessThanOrEqual(bbox.minY)
+      expect(node.maxX).toBeGreaterThanOrEqual(bbox.maxX)
+      expect(node.maxY).toBeGreaterThanOrEqual(bbox.maxY)
+      expect((node.maxX - node.minX) * (node.maxY - node.minY)).toBe(smallest)
+      expect(node.count).toBeGreaterThan(0)
+    }
+  })
+
+  test("returns the root for a single-node tree and null outside it", () => {
+    const tree = new RBushWasm()
+    tree.load([
+      { minX: 0, minY: 0, maxX: 1, maxY: 1 },
+      { minX: 4, minY: 4, maxX: 5, maxY: 6 },
+    ])
+    expect(tree.tightestContainingNode({ minX: 0.5, minY: 0.5, maxX: 4.5, maxY: 4.5 })).toEqual({
+      minX: 0,
+      minY: 0,
+      maxX: 5,
+      maxY: 6,
+      count: 2,
+    })
+    expect(tree.tightestContainingNode({ minX: 3, minY: 3, maxX: 7, maxY: 7 })).toBeNull()
+    expect(new RBushWasm().tightestContainingNode({ minX: 0, minY: 0, maxX: 1, maxY: 1 })).toBeNull()
+  })
+})
+
+describe("RBush Search Line", () => {
+  test("returns items whose bbox crosses the line, inclusive", () => {
+    const tree = new RBushWasm(4)
+    const items = []
+    for (let i = 0; i < 150; i++) {
+      const x = Math.random() * 100
+      const y = Math.random() * 100
+      items.push({ minX: x, minY: y, maxX: x + Math.random() * 10, maxY: y + Math.random() * 10 })
+    }
+    tree.load(items)
+
+    for (const at of [0, 25.5, 50, 99]) {
+      const vertical = items.filter((item) => item.minX <= at && at <= item.maxX)
+      const horizontal = items.filter((item) => item.minY <= at && at <= item.maxY)
+      expect(new Set(tree.searchVLine(at))).toEqual(new Set(vertical))
+      expect(tree.searchVLine(at).length).toBe(vertical.length)
+      expect(new Set(tree.searchHLine(at))).toEqual(new Set(horizontal))
+      expect(tree.searchHLine(at).length).toBe(horizontal.length)
+    }
+  })
+
+  test("counts an edge on the line as crossing it", () => {
+    const tree = new RBushWasm()
+    const item = { minX: 2, minY: 3, maxX: 4, maxY: 5 }
+    tree.insert(item)
+    expect(tree.searchVLine(2)).toEqual([item])
+    expect(tree.searchVLine(4)).toEqual([item])
+    expect(tree.searchVLine(4.001)).toEqual([])
+    expect(tree.searchHLine(3)).toEqual([item])
+    expect(tree.searchHLine(5)).toEqual([item])
+    expect(tree.searchHLine(2.999)).toEqual([])
+    expect(new RBushWasm().searchVLine(0)).toEqual([])
+  })
+})
+
+describe("RBush Fragmentation Score", () => {
+  test("counts intersecting sibling node pairs", () => {
+    const tree = new RBushWasm(4)
+    for (let i = 0; i < 300; i++) {
+      const x = Math.random() * 100
+      const y = Math.random() * 100
+      tree.insert({ minX: x, minY: y, maxX: x + Math.random() * 10, maxY: y + Math.random() * 10 })
+    }
+    const intersects = (a, b) =>
+      a.minX <= b.maxX && a.minY <= b.maxY && a.maxX >= b.minX && a.maxY >= b.minY
+
+    let expected = 0
+    const stack = [tree.toJSON()]
+    while (stack.length) {
+      const node = stack.pop()
+      if (node.height <= 1) continue
+      for (let i = 0; i < node.children.length; i++) {
+        for (let j = i + 1; j < node.children.length; j++) {
+          if (intersects(node.children[i], node.children[j])) expected++
+        }
+      }
+      stack.push(...node.children)
+    }
+    expect(tree.fragmentationScore()).toBe(expected)
+  })
+
+  test("ignores overlapping items and disjoint nodes", () => {
+    const tree = new RBushWasm()
+    tree.load([
+      { minX: 0, minY: 0, maxX: 2, maxY: 2 },
+      { minX: 1, minY: 1, maxX: 3, maxY: 3 },
+    ])
+    expect(tree.fragmentationScore()).toBe(0)
+
+    const line = new RBushWasm(4)
+    const items = []
+    for (let i = 0; i < 100; i++) items.push({ minX: i, minY: i, maxX: i + 0.5, maxY: i + 0.5 })
+    line.load(items)
+    expect(line.height()).toBeGreaterThan(1)
+    expect(line.fragmentationScore()).toBe(0)
+    expect(new RBushWasm().fragmentationScore()).toBe(0)
+  })
+})
+
+describe("RBush Search Bfs", () => {
+  test("returns search's matches grouped by leaf node in left-to-right order", () => {
+    const tree = new RBushWasm(4)
+    const items = []
+    for (let i = 0; i < 200; i++) {
+      const x = Math.random() * 100
+      const y = Math.random() * 100
+      items.push({ minX: x, minY: y, maxX: x + 3, maxY: y + 3 })
+    }
+    tree.load(items)
+    const bbox = { minX: 25, minY: 25, maxX: 75, maxY: 60 }
+    const intersects = (a) =>
+      a.minX <= bbox.maxX && a.minY <= bbox.maxY && a.maxX >= bbox.minX && a.maxY >= bbox.minY
+
+    let level = [tree.toJSON()]
+    while (!level[0].leaf) level = level.flatMap((node) => node.children)
+    const expected = level.flatMap((node) => node.children.filter(intersects))
+
+    const result = tree.searchBfs(bbox)
+    expect(result).toEqual(expected)
+    expect(new Set(result)).toEqual(new Set(tree.search(bbox)))
+    expect(tree.searchBfs({ minX: 200, minY: 200, maxX: 300, maxY: 300 })).toEqual([])
+    expect(new RBushWasm().searchBfs(bbox)).toEqual([])
+  })
+})
+
+describe("RBush Nearest To Points", () => {
+  test("returns the item nearest to the centroid of the points", () => {
+    const tree = new RBushWasm(4)
+    const items = []
+    for (let i = 0; i < 100; i++) {
+      const x = Math.random() * 100
+      const y = Math.random() * 100
+      items.push({ minX: x, minY: y, maxX: x + 1, maxY: y + 1 })
+    }
+    tree.load(items)
+
+    const coords = new Float64Array([10, 20, 30, 60, 80, 40, 40, 0])
+    expect(tree.nearestToPoints(coords)).toBe(tree.knn(40, 30, 1)[0])
+  })
+
+  test("uses the current metric and ignores a trailing odd value", () => {
+    const tree = new RBushWasm()
+    const diagonal = { minX: 3, minY: 3, maxX: 3, maxY: 3 }
+    const straight = { minX: 5, minY: 0, maxX: 5, maxY: 0 }
+    tree.load([diagonal, straight])
+
+    // Centroid (0, 0): diagonal is nearer by Euclidean distance, straight by Manhattan.
+    const coords = new Float64Array([-1, -1, 1, 1, 100])
+    expect(tree.nearestToPoints(coords)).toBe(diagonal)
+    tree.setMetric("manhattan")
+    expect(tree.nearestToPoints(coords)).toBe(straight)
+  })
+
+  test("returns null for an empty tree or no points", () => {
+    const tree = new RBushWasm()
+    expect(tree.nearestToPoints(new Float64Array([1, 2]))).toBeNull()
+    tree.insert({ minX: 0, minY: 0, maxX: 1, maxY: 1 })
+    expect(tree.nearestToPoints(new Float64Array([]))).toBeNull()
+    expect(tree.nearestToPoints(new Float64Array([7]))).toBeNull()
+  })
+})
+
+describe("RBush Search Enclosing Circle", () => {
+  test("returns the smallest circle around the matching corners", () => {
+    const tree = new RBushWasm()
+    tree.load([
+      { minX: 0, minY: 0, maxX: 1, maxY: 1 },
+      { minX: 1, minY: 1, maxX: 2, maxY: 2 },
+      { minX: 0.5, minY: 0.5, maxX: 1.5, maxY: 1.5 },
+      { minX: 10, minY: 10, maxX: 11, maxY: 11 },
+    ])
+
+    const circle = tree.searchEnclosingCircle({ minX: 0, minY: 0, maxX: 2, maxY: 2 })
+    expect(circle.cx).toBeCloseTo(1)
+    expect(circle.cy).toBeCloseTo(1)
+    expect(circle.radius).toBeCloseTo(Math.SQRT2)
+  })
+
+  test("contains every matching corner, with at least two on the circle", () => {
+    const tree = new RBushWasm(4)
+    const items = []
+    for (let i = 0; i < 80; i++) {
+      const x = Math.random() * 100
+      const y = Math.random() * 100
+      items.push({ minX: x, minY: y, maxX: x + Math.random() * 5, maxY: y + Math.random() * 5 })
+    }
+    tree.load(items)
+    const bbox = { minX: 20, minY: 20, maxX: 70, maxY: 70 }
+
+    const { cx, cy, radius } = tree.searchEnclosingCircle(bbox)
+    const distances = tree
+      .search(bbox)
+      .flatMap((b) => [[b.minX, b.minY], [b.maxX, b.minY], [b.maxX, b.maxY], [b.minX, b.maxY]])
+      .map(([x, y]) => Math.hypot(x - cx, y - cy))
+    for (const d of distances) expect(d).toBeLessThanOrEqual(radius + 1e-9)
+    expect(distances.filter((d) => Math.abs(d - radius) < 1e-9).length).toBeGreaterThanOrEqual(2)
+  })
+
+  test("gives a zero radius for a point and null when nothing matches", () => {
+    const tree = new RBushWasm()
+    tree.insert({ minX: 3, minY: 4, maxX: 3, maxY: 4 })
+    expect(tree.searchEnclosingCircle({ minX: 0, minY: 0, maxX: 5, maxY: 5 })).toEqual({ cx: 3, cy: 4, radius: 0 })
+    expect(tree.searchEnclosingCircle({ minX: 10, minY: 10, maxX: 20, maxY: 20 })).toBeNull()
+  })
+})
+
+describe("RBush Distinct Coords", () => {
+  test("returns the sorted distinct values of the chosen side", () => {
+    const tree = new RBushWasm(4)
+    tree.load([
+      { minX: 3, minY: 2, maxX: 4, maxY: 9 },
+      { minX: 1, minY: 2, maxX: 4, maxY: 5 },
+      { minX: 3, minY: 0, maxX: 6, maxY: 5 },
+      { minX: 0, minY: 7, maxX: 1, maxY: 8 },
+      { minX: 1, minY: 2, maxX: 2, maxY: 3 },
+    ])
+    expect(tree.distinctCoords(0)).toEqual(new Float64Array([0, 1, 3]))
+    expect(tree.distinctCoords(1)).toEqual(new Float64Array([0, 2, 7]))
+    expect(tree.distinctCoords(0, true)).toEqual(new Float64Array([1, 2, 4, 6]))
+    expect(tree.distinctCoords(1, true)).toEqual(new Float64Array([3, 5, 8, 9]))
+  })
+
+  test("collapses each run of close values to its smallest", () => {
+    const tree = new RBushWasm()
+    for (const x of [1.2, 0, 3, 1, 1.05]) tree.insert({ minX: x, minY: 0, maxX: x, maxY: 0 })
+    expect(tree.distinctCoords(0)).toEqual(new Float64Array([0, 1, 1.05, 1.2, 3]))
+    expect(tree.distinctCoords(0, false, 0.25)).toEqual(new Float64Array([0, 1, 3]))
+  })
+
+  test("throws on an unknown axis and is empty for an empty tree", () => {
+    const tree = new RBushWasm()
+    expect(tree.distinctCoords(0)).toEqual(new Float64Array([]))
+    expect(() => tree.distinctCoords(2)).toThrow()
+  })
+})
+
+describe("RBush All Nn Distances", () => {
+  test("pairs every item with the gap to its closest other item", () => {
+    const tree = new RBushWasm(4)
+    const items = []
+    for (let i = 0; i < 120; i++) {
+      const x = Math.random() * 100
+      const y = Math.random() * 100
+      items.push({ minX: x, minY: y, maxX: x + Math.random() * 3, maxY: y + Math.random() * 3 })
+    }
+    tree.load(items)
+    const gap = (a, b) =>
+      Math.hypot(
+        Math.max(0, a.minX - b.maxX, b.minX - a.maxX),
+        Math.max(0, a.minY - b.maxY, b.minY - a.maxY)
+      )
+
+    const result = tree.allNnDistances()
+    expect(result.length).toBe(120)
+    expect(new Set(result.map((entry) => entry.data))).toEqual(new Set(items))
+    for (const { data, nnDistance } of result) {
+      const expected = Math.min(...items.filter((other) => other !== data).map((other) => gap(data, other)))
+      expect(nnDistance).toBeCloseTo(expected, 9)
+    }
+  })
+
+  test("is 0 for touching items and null for a lone item", () => {
+    const a = { minX: 0, minY: 0, maxX: 1, maxY: 1 }
+    const b = { minX: 1, minY: 0, maxX: 2, maxY: 1 }
+    const c = { minX: 5, minY: 0, maxX: 6, maxY: 1 }
+    const tree = new RBushWasm()
+    tree.load([a, b, c])
+    const byItem = new Map(tree.allNnDistances().map((entry) => [entry.data, entry.nnDistance]))
+    expect(byItem.get(a)).toBe(0)
+    expect(byItem.get(b)).toBe(0)
+    expect(byItem.get(c)).toBe(3)
+
+    const lone = new RBushWasm()
+    lone.insert(a)
+    expect(lone.allNnDistances()).toEqual([{ data: a, nnDistance: null }])
+    expect(new RBushWasm().allNnDistances()).toEqual([])
+  })
+})
+
+describe("RBush Snapshots", () => {
+  test("a snapshot keeps its results through later mutations", () => {
+    const tree = new RBushWasm(4)
+    const items = []
+    for (let i = 0; i < 50; i++) items.push({ minX: i, minY: i, maxX: i + 1, maxY: i + 1 })
+    tree.load(items)
+    const bbox = { minX: 10, minY: 10, maxX: 30, maxY: 30 }
+    const before = tree.search(bbox)
+    const version = tree.snapshot()
+
+    tree.insert({ minX: 20, minY: 20, maxX: 21, maxY: 21 })
+    for (const item of items.slice(15, 25)) tree.remove(item)
+    expect(new Set(tree.searchVersion(version, bbox))).toEqual(new Set(before))
+
+    tree.clear()
+    expect(tree.search(bbox)).toEqual([])
+    expect(tree.searchVersion(version, bbox).length).toBe(before.length)
+    expect(new Set(tree.searchVersion(version, bbox))).toEqual(new Set(before))
+  })
+
+  test("versions are independent of each other", () => {
+    const tree = new RBushWasm()
+    const a = { minX: 0, minY: 0, maxX: 1, maxY: 1 }
+    const b = { minX: 2, minY: 2, maxX: 3, maxY: 3 }
+    const bbox = { minX: 0, minY: 0, maxX: 5, maxY: 5 }
+    tree.insert(a)
+    const first = tree.snapshot()
+    tree.insert(b)
+    const second = tree.snapshot()
+    expect(first).not.toBe(second)
+    expect(tree.searchVersion(first, bbox)).toEqual([a])
+    expect(new Set(tree.searchVersion(second, bbox))).toEqual(new Set([a, b]))
+  })
+
+  test("searchVersion throws for unknown or dropped versions", () => {
+    const tree = new RBushWasm()
+    tree.insert({ minX: 0, minY: 0, maxX: 1, maxY: 1 })
+    const bbox = { minX: 0, minY: 0, maxX: 1, maxY: 1 }
+    const version = tree.snapshot()
+    expect(() => tree.searchVersion(version + 100n, bbox)).toThrow("unknown snapshot version")
+
+    expect(tree.dropSnapshot(version)).toBe(true)
+    expect(tree.dropSnapshot(version)).toBe(false)
+    expect(() => tree.searchVersion(version, bbox)).toThrow("unknown snapshot version")
+    expect(tree.dropSnapshot(version + 100n)).toBe(false)
+  })
+})
+
+describe("RBush Search Capacity", () => {
+  test("returns the same items as search whatever the expected count", () => {
+    const tree = new RBushWasm(4)
+    const items = []
+    for (let i = 0; i < 200; i++) {
+      const x = Math.random() * 100
+      const y = Math.random() * 100
+      items.push({ minX: x, minY: y, maxX: x + 2, maxY: y + 2 })
+    }
+    tree.load(items)
+    const bbox = { minX: 20, minY: 20, maxX: 60, maxY: 60 }
+    const expected = tree.search(bbox)
+    expect(expected.length).toBeGreaterThan(1)
+
+    for (const n of [0, 1, expected.length - 1, expected.length, expected.length + 1, 1000]) {
+      const result = tree.searchCapacity(bbox, n)
+      expect(result.length).toBe(expected.length)
+      expect(result).toEqual(expected)
+      expect(new Set(result)).toEqual(new Set(expected))
+    }
+  })
+
+  test("returns an empty array when nothing matches", () => {
+    const tree = new RBushWasm()
+    tree.insert({ minX: 0, minY: 0, maxX: 1, maxY: 1 })
+    const result = tree.searchCapacity({ minX: 5, minY: 5, maxX: 6, maxY: 6 }, 10)
+    expect(result).toEqual([])
+    expect(result.length).toBe(0)
+  })
+})
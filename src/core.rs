@@ -0,0 +1,1261 @@
+// Tree logic shared by the WASM `RBush` wrapper and native Rust users. Holds
+// any `T` that can report its bbox through `BBox`.
+
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::ops::Add;
+
+/// Types that can be stored in an `RBushCore` indexed by boxes of type `B`.
+pub trait BBox<B = Rect> {
+    fn bbox(&self) -> B;
+}
+
+/// Axis-aligned box type an `RBushCore` is built over: `Rect` in 2D, or any
+/// box with `AXES` axes. Splits and bulk loads order children by their
+/// `min` edge along each axis in turn.
+pub trait Bounds: Copy + PartialEq {
+    /// Type of areas and margins.
+    type Measure: Measure;
+    /// Number of axes, at least 1.
+    const AXES: usize;
+
+    /// The empty box, which any `extend` replaces.
+    fn new_empty() -> Self;
+    fn extend(&mut self, other: &Self);
+    /// Whether the boxes overlap, edges inclusive.
+    fn intersects(&self, other: &Self) -> bool;
+    fn contains(&self, other: &Self) -> bool;
+    /// Area (or volume) of the box.
+    fn area(&self) -> Self::Measure;
+    /// Sum of the box's edge lengths, one per axis.
+    fn margin(&self) -> Self::Measure;
+    /// Area `self` would gain by growing to include `other`.
+    fn enlargement(&self, other: &Self) -> Self::Measure;
+    /// Area shared by the boxes, zero when they are disjoint.
+    fn intersection_area(&self, other: &Self) -> Self::Measure;
+    /// Orders boxes by their `min` edge along `axis`.
+    fn cmp_min(&self, other: &Self, axis: usize) -> Ordering;
+}
+
+/// Areas and margins reported by a `Bounds` type.
+pub trait Measure: Copy + PartialOrd + Add<Output = Self> {
+    /// Starting value of minimum searches, no smaller than any real measure.
+    const WORST: Self;
+    /// Maps an overflowed measure (infinite or NaN) to the largest finite
+    /// one, so it still ranks last instead of comparing false.
+    fn saturate(self) -> Self;
+}
+
+impl Measure for f64 {
+    const WORST: Self = f64::INFINITY;
+
+    fn saturate(self) -> Self {
+        finite_or_max(self)
+    }
+}
+
+impl Measure for i128 {
+    const WORST: Self = i128::MAX;
+
+    fn saturate(self) -> Self {
+        self
+    }
+}
+
+/// Axis-aligned bounding box, edges inclusive.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+    pub min_x: f64,
+    pub min_y: f64,
+    pub max_x: f64,
+    pub max_y: f64,
+}
+
+impl Rect {
+    /// The empty box, which any `extend` replaces.
+    pub fn new_empty() -> Self {
+        Rect {
+            min_x: f64::INFINITY,
+            min_y: f64::INFINITY,
+            max_x: f64::NEG_INFINITY,
+            max_y: f64::NEG_INFINITY,
+        }
+    }
+
+    /// Box with the given edges.
+    pub fn new(min_x: f64, min_y: f64, max_x: f64, max_y: f64) -> Self {
+        Rect {
+            min_x,
+            min_y,
+            max_x,
+            max_y,
+        }
+    }
+
+    pub(crate) fn from_flat(data: &[f64]) -> Self {
+        Rect {
+            min_x: data[0],
+            min_y: data[1],
+            max_x: data[2],
+            max_y: data[3],
+        }
+    }
+
+    pub fn area(&self) -> f64 {
+        (self.max_x - self.min_x) * (self.max_y - self.min_y)
+    }
+
+    pub(crate) fn margin(&self) -> f64 {
+        (self.max_x - self.min_x) + (self.max_y - self.min_y)
+    }
+
+    // Area `self` would gain by growing to include `other`. Computed from the
+    // per-axis growth instead of `enlarged_area() - area()`, which cancels
+    // catastrophically when both areas are huge and nearly equal.
+    pub(crate) fn enlargement(&self, other: &Rect) -> f64 {
+        let width = self.max_x - self.min_x;
+        let height = self.max_y - self.min_y;
+        let grow_x = f64::max(0.0, other.max_x - self.max_x) + f64::max(0.0, self.min_x - other.min_x);
+        let grow_y = f64::max(0.0, other.max_y - self.max_y) + f64::max(0.0, self.min_y - other.min_y);
+
+        let mut enlargement = 0.0;
+        if grow_x > 0.0 {
+            enlargement += grow_x * height;
+        }
+        if grow_y > 0.0 {
+            enlargement += grow_y * (width + grow_x);
+        }
+        enlargement
+    }
+
+    pub(crate) fn intersection_area(&self, other: &Rect) -> f64 {
+        let min_x = f64::max(self.min_x, other.min_x);
+        let min_y = f64::max(self.min_y, other.min_y);
+        let max_x = f64::min(self.max_x, other.max_x);
+        let max_y = f64::min(self.max_y, other.max_y);
+
+        let width = max_x - min_x;
+        let height = max_y - min_y;
+        // Checked separately so an overflowed width never meets a zero height
+        // (`inf * 0` is NaN).
+        if width <= 0.0 || height <= 0.0 {
+            return 0.0;
+        }
+        width * height
+    }
+
+    pub fn contains(&self, other: &Rect) -> bool {
+        self.min_x <= other.min_x
+            && self.min_y <= other.min_y
+            && other.max_x <= self.max_x
+            && other.max_y <= self.max_y
+    }
+
+    pub fn center(&self) -> (f64, f64) {
+        (
+            (self.min_x + self.max_x) / 2.0,
+            (self.min_y + self.max_y) / 2.0,
+        )
+    }
+
+    // Per-axis gaps from the point to the rectangle, 0 on an axis the point
+    // lies within.
+    pub(crate) fn point_gap(&self, x: f64, y: f64) -> (f64, f64) {
+        let dx = f64::max(0.0, f64::max(self.min_x - x, x - self.max_x));
+        let dy = f64::max(0.0, f64::max(self.min_y - y, y - self.max_y));
+        (dx, dy)
+    }
+
+    // Squared gap between two rectangles, 0 when they touch or overlap.
+    pub(crate) fn box_dist_sq(&self, other: &Rect) -> f64 {
+        let dx = f64::max(0.0, f64::max(self.min_x - other.max_x, other.min_x - self.max_x));
+        let dy = f64::max(0.0, f64::max(self.min_y - other.max_y, other.min_y - self.max_y));
+        dx * dx + dy * dy
+    }
+
+    pub fn contains_point(&self, x: f64, y: f64) -> bool {
+        self.min_x <= x && x <= self.max_x && self.min_y <= y && y <= self.max_y
+    }
+
+    pub fn intersects(&self, other: &Rect) -> bool {
+        other.min_x <= self.max_x
+            && other.min_y <= self.max_y
+            && other.max_x >= self.min_x
+            && other.max_y >= self.min_y
+    }
+
+    // Maps `x -> x * sx + dx`, `y -> y * sy + dy`, swapping min/max on
+    // negative scales so the rectangle stays well-formed.
+    pub(crate) fn transform(&mut self, sx: f64, sy: f64, dx: f64, dy: f64) {
+        let (x0, x1) = (self.min_x * sx + dx, self.max_x * sx + dx);
+        let (y0, y1) = (self.min_y * sy + dy, self.max_y * sy + dy);
+        self.min_x = f64::min(x0, x1);
+        self.max_x = f64::max(x0, x1);
+        self.min_y = f64::min(y0, y1);
+        self.max_y = f64::max(y0, y1);
+    }
+
+    // The box grown by `pad` on every side.
+    pub(crate) fn padded(&self, pad: f64) -> Rect {
+        Rect {
+            min_x: self.min_x - pad,
+            min_y: self.min_y - pad,
+            max_x: self.max_x + pad,
+            max_y: self.max_y + pad,
+        }
+    }
+
+    pub fn extend(&mut self, other: &Rect) {
+        self.min_x = f64::min(self.min_x, other.min_x);
+        self.min_y = f64::min(self.min_y, other.min_y);
+        self.max_x = f64::max(self.max_x, other.max_x);
+        self.max_y = f64::max(self.max_y, other.max_y);
+    }
+}
+
+impl Bounds for Rect {
+    type Measure = f64;
+    const AXES: usize = 2;
+
+    fn new_empty() -> Self {
+        Rect::new_empty()
+    }
+
+    fn extend(&mut self, other: &Self) {
+        Rect::extend(self, other)
+    }
+
+    fn intersects(&self, other: &Self) -> bool {
+        Rect::intersects(self, other)
+    }
+
+    fn contains(&self, other: &Self) -> bool {
+        Rect::contains(self, other)
+    }
+
+    fn area(&self) -> f64 {
+        Rect::area(self)
+    }
+
+    fn margin(&self) -> f64 {
+        Rect::margin(self)
+    }
+
+    fn enlargement(&self, other: &Self) -> f64 {
+        Rect::enlargement(self, other)
+    }
+
+    fn intersection_area(&self, other: &Self) -> f64 {
+        Rect::intersection_area(self, other)
+    }
+
+    fn cmp_min(&self, other: &Self, axis: usize) -> Ordering {
+        match axis {
+            0 => self.min_x.total_cmp(&other.min_x),
+            _ => self.min_y.total_cmp(&other.min_y),
+        }
+    }
+}
+
+pub(crate) fn finite_or_max(value: f64) -> f64 {
+    if value.is_nan() || value == f64::INFINITY {
+        f64::MAX
+    } else {
+        value
+    }
+}
+
+// Running operation counters, see `RBush::op_stats`.
+#[derive(Default)]
+pub(crate) struct OpStats {
+    pub(crate) inserts: usize,
+    pub(crate) splits: usize,
+    pub(crate) root_splits: usize,
+    pub(crate) removals: usize,
+    pub(crate) reinserts: usize,
+}
+
+#[derive(Clone)]
+pub(crate) struct Entry<T, B = Rect> {
+    pub(crate) bbox: B,
+    // The item on leaves, `None` on nodes.
+    pub(crate) data: Option<T>,
+    pub(crate) is_leaf: bool,
+    pub(crate) height: usize,
+    pub(crate) children: Vec<Entry<T, B>>,
+    // Item timestamp on leaves, newest timestamp in the subtree on nodes.
+    // NEG_INFINITY when no timestamp was given.
+    pub(crate) time: f64,
+    // Stable id of a pinned leaf, 0 when unpinned.
+    pub(crate) pin: u64,
+    // Category mask on leaves, OR of all masks in the subtree on nodes.
+    pub(crate) tags: u32,
+    // Number of items in the subtree (1 for an item itself).
+    pub(crate) count: usize,
+    // True extent of an item, which `bbox` encloses when the item was
+    // inserted padded; unused on nodes.
+    pub(crate) extent: B,
+    // Padding `insert_padded` added around `extent` on every side, kept so
+    // that moving the item re-pads its new box; 0 on nodes and plain items.
+    pub(crate) pad: f64,
+    // Set on nodes by `freeze_region`; inserts and removals never change a
+    // frozen node.
+    pub(crate) frozen: bool,
+}
+
+impl<T> Entry<T> {
+    pub(crate) fn transform(&mut self, sx: f64, sy: f64, dx: f64, dy: f64) {
+        self.bbox.transform(sx, sy, dx, dy);
+        self.extent.transform(sx, sy, dx, dy);
+        // One pad serves both axes, so it follows the larger scale.
+        self.pad *= f64::max(sx.abs(), sy.abs());
+        for child in &mut self.children {
+            child.transform(sx, sy, dx, dy);
+        }
+    }
+}
+
+impl<T, B: Bounds> Entry<T, B> {
+    // Whether the node or any node below it is frozen. Underfull nodes that
+    // hold frozen nodes are kept rather than dissolved into reinserts.
+    pub(crate) fn holds_frozen(&self) -> bool {
+        self.frozen
+            || self
+                .children
+                .iter()
+                .any(|child| !child.is_leaf && child.holds_frozen())
+    }
+
+    pub(crate) fn leaf_with_bbox(bbox: B, data: T) -> Self {
+        Entry {
+            bbox,
+            data: Some(data),
+            is_leaf: true,
+            height: 1,
+            children: vec![],
+            time: f64::NEG_INFINITY,
+            pin: 0,
+            tags: 0,
+            count: 1,
+            extent: bbox,
+            pad: 0.0,
+            frozen: false,
+        }
+    }
+
+    pub(crate) fn new_node(children: Vec<Entry<T, B>>) -> Self {
+        let mut node = Entry {
+            bbox: B::new_empty(),
+            data: None,
+            is_leaf: false,
+            height: 1,
+            children,
+            time: f64::NEG_INFINITY,
+            pin: 0,
+            tags: 0,
+            count: 0,
+            extent: B::new_empty(),
+            pad: 0.0,
+            frozen: false,
+        };
+        node.calc_bbox();
+        node
+    }
+
+    // Recomputes the node's bbox and subtree aggregates from its children.
+    pub(crate) fn calc_bbox(&mut self) {
+        let children = std::mem::take(&mut self.children);
+        self.bbox = B::new_empty();
+        self.time = f64::NEG_INFINITY;
+        self.tags = 0;
+        self.count = 0;
+        for child in &children {
+            self.extend(child);
+        }
+        self.children = children;
+    }
+
+    // Folds a (new) child into the node's bbox and subtree aggregates.
+    pub(crate) fn extend(&mut self, child: &Entry<T, B>) {
+        self.bbox.extend(&child.bbox);
+        self.time = f64::max(self.time, child.time);
+        self.tags |= child.tags;
+        self.count += child.count;
+    }
+
+    // The box item-level queries test: an item's true extent, a node's bbox.
+    pub(crate) fn shape(&self) -> &B {
+        if self.is_leaf { &self.extent } else { &self.bbox }
+    }
+}
+
+impl<T: PartialEq, B: Bounds> Entry<T, B> {
+    pub(crate) fn structurally_equals(&self, other: &Entry<T, B>) -> bool {
+        self.is_leaf == other.is_leaf
+            && self.height == other.height
+            && self.bbox == other.bbox
+            && (!self.is_leaf || self.data == other.data)
+            && self.children.len() == other.children.len()
+            && self
+                .children
+                .iter()
+                .zip(&other.children)
+                .all(|(a, b)| a.structurally_equals(b))
+    }
+}
+
+// Placeholder left behind when `_build` moves entries out of its buffer.
+impl<T, B: Bounds> Default for Entry<T, B> {
+    fn default() -> Self {
+        Entry::new_node(vec![])
+    }
+}
+
+// Smallest `r` with `r^d >= m^k`, i.e. `ceil(m^(k/d))` free of the rounding
+// a float root would bring at exact powers.
+fn ceil_root(m: usize, k: usize, d: usize) -> usize {
+    let target = (m as u128).saturating_pow(k as u32);
+    let mut r = ((m as f64).powf(k as f64 / d as f64).floor() as u128).max(1);
+    while r.saturating_pow(d as u32) < target {
+        r += 1;
+    }
+    while r > 1 && (r - 1).saturating_pow(d as u32) >= target {
+        r -= 1;
+    }
+    r as usize
+}
+
+// Default minimum node fill, as a ratio of `max_entries`.
+const DEFAULT_FILL_FACTOR: f64 = 0.4;
+
+/// R-tree over plain Rust values, the engine behind the WASM `RBush`. Indexes
+/// `Rect`s unless given another `Bounds` type `B`.
+pub struct RBushCore<T, B = Rect> {
+    pub(crate) root: Entry<T, B>,
+    pub(crate) max_entries: usize,
+    pub(crate) min_entries: usize,
+    pub(crate) stats: OpStats,
+}
+
+impl<T: BBox<B>, B: Bounds> RBushCore<T, B> {
+    /// Adds one item.
+    pub fn insert(&mut self, item: T) {
+        let entry = Entry::leaf_with_bbox(item.bbox(), item);
+        self.stats.inserts += 1;
+        self._insert_at_level(entry, 0);
+    }
+
+    /// Adds many items at once with OMT bulk loading, which builds a better
+    /// tree faster than inserting them one by one.
+    pub fn bulk_load(&mut self, items: Vec<T>) {
+        let entries: Vec<Entry<T, B>> = items
+            .into_iter()
+            .map(|item| Entry::leaf_with_bbox(item.bbox(), item))
+            .collect();
+        self.stats.inserts += entries.len();
+        self.load_entries(entries);
+    }
+}
+
+impl<T: BBox<B> + PartialEq, B: Bounds> RBushCore<T, B> {
+    /// Removes the first item equal to `item` and returns it, or `None` when
+    /// the tree holds no such item.
+    pub fn remove(&mut self, item: &T) -> Option<T> {
+        self.remove_where(&item.bbox(), |data| data == item)
+    }
+}
+
+impl<T, B: Bounds> RBushCore<T, B> {
+    /// Empty tree with nodes of at most `max_entries` children (at least 4).
+    pub fn new(max_entries: usize) -> Self {
+        Self::with_fill_factor(max_entries, DEFAULT_FILL_FACTOR)
+    }
+
+    /// Like `new`, with non-root nodes kept at least `fill` full before they
+    /// are dissolved on removal. `fill` is a ratio of `max_entries` clamped
+    /// to `(0, 0.5]` (NaN means the default 0.4), and every node keeps at
+    /// least 2 children. Lower values mean less reinsertion churn under
+    /// heavy deletion, higher ones denser nodes.
+    pub fn with_fill_factor(max_entries: usize, fill: f64) -> Self {
+        let m = max_entries.max(4);
+        let fill = if fill.is_nan() { DEFAULT_FILL_FACTOR } else { fill.clamp(0.0, 0.5) };
+        let min = (m as f64 * fill).ceil().max(2.0) as usize;
+        RBushCore {
+            root: Entry::new_node(vec![]),
+            max_entries: m,
+            min_entries: min,
+            stats: OpStats::default(),
+        }
+    }
+
+    /// Most children a node holds before it splits.
+    pub fn max_entries(&self) -> usize {
+        self.max_entries
+    }
+
+    /// Fewest children a non-root node keeps before removal dissolves it.
+    pub fn min_entries(&self) -> usize {
+        self.min_entries
+    }
+
+    /// Number of items in the tree.
+    pub fn len(&self) -> usize {
+        self.root.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.count == 0
+    }
+
+    /// Removes every item.
+    pub fn clear(&mut self) {
+        self.root = Entry::new_node(vec![]);
+        self.stats = OpStats::default();
+    }
+
+    /// Items whose bbox intersects `bbox`, edges inclusive.
+    pub fn search(&self, bbox: &B) -> Vec<&T> {
+        let mut result = Vec::new();
+        if !bbox.intersects(&self.root.bbox) {
+            return result;
+        }
+        let mut stack = vec![&self.root];
+        while let Some(node) = stack.pop() {
+            for child in &node.children {
+                if child.is_leaf {
+                    if bbox.intersects(child.shape()) {
+                        result.extend(child.data.as_ref());
+                    }
+                } else if bbox.contains(&child.bbox) {
+                    Self::push_items(child, &mut result);
+                } else if bbox.intersects(&child.bbox) {
+                    stack.push(child);
+                }
+            }
+        }
+        result
+    }
+
+    /// Whether any item's bbox intersects `bbox`, edges inclusive. Stops at
+    /// the first item hit or node lying wholly inside `bbox`.
+    pub fn collides(&self, bbox: &B) -> bool {
+        let mut stack = vec![&self.root];
+        while let Some(node) = stack.pop() {
+            if !bbox.intersects(&node.bbox) {
+                continue;
+            }
+            for child in &node.children {
+                if bbox.intersects(child.shape()) {
+                    if child.is_leaf || bbox.contains(child.shape()) {
+                        return true;
+                    }
+                    stack.push(child);
+                }
+            }
+        }
+        false
+    }
+
+    /// Every item in the tree.
+    pub fn all(&self) -> Vec<&T> {
+        let mut result = Vec::with_capacity(self.root.count);
+        Self::push_items(&self.root, &mut result);
+        result
+    }
+
+    fn push_items<'a>(node: &'a Entry<T, B>, acc: &mut Vec<&'a T>) {
+        for child in &node.children {
+            if child.is_leaf {
+                acc.extend(child.data.as_ref());
+            } else {
+                Self::push_items(child, acc);
+            }
+        }
+    }
+
+    // Removes the first item for which `is_item` holds, looking only under
+    // nodes containing `bbox`, and condenses the tree after it.
+    pub(crate) fn remove_where(&mut self, bbox: &B, mut is_item: impl FnMut(&T) -> bool) -> Option<T> {
+        let mut reinsert = Vec::new();
+        let removed = Self::remove_from_node(&mut self.root, &mut is_item, bbox, self.min_entries, &mut reinsert);
+        let count = usize::from(removed.is_some());
+        self.condense(reinsert, count);
+        removed.and_then(|entry| entry.data)
+    }
+
+    // Reinserts the items orphaned by a removal and shrinks the root back
+    // down to its lowest level with more than one child.
+    pub(crate) fn condense(&mut self, reinsert: Vec<Entry<T, B>>, count: usize) {
+        if self.root.children.is_empty() {
+            self.root = Entry::new_node(vec![]);
+        }
+        self.stats.removals += count;
+        self.stats.reinserts += reinsert.len();
+        for i in reinsert {
+            self._insert_at_level(i, 0);
+        }
+        while self.root.height > 1 && self.root.children.len() == 1 {
+            self.root = self.root.children.pop().unwrap();
+        }
+    }
+
+    // `level` is the height of the entry being inserted (0 for items), so it
+    // is linked under the first node of height `level + 1` on its path.
+    pub(crate) fn _insert_at_level(&mut self, item: Entry<T, B>, level: usize) {
+        let split = Self::insert_recursive(
+            &mut self.root,
+            item,
+            level,
+            self.max_entries,
+            self.min_entries,
+            &mut self.stats,
+        );
+        if let Some(new_node) = split {
+            self._split_root(new_node);
+        }
+    }
+
+    // Links every entry at `level` (as in `_insert_at_level`) in one pass:
+    // each is pushed into the parent `choose_subtree` picks without touching
+    // the nodes on the way, then the affected paths are walked once,
+    // bottom-up, splitting overfull nodes and recomputing bboxes.
+    pub(crate) fn graft_entries(&mut self, entries: Vec<Entry<T, B>>, level: usize) {
+        let mut touched = HashSet::new();
+        for entry in entries {
+            let mut node = &mut self.root;
+            let mut path = Vec::new();
+            while node.height - 1 != level {
+                touched.insert(path.clone());
+                let i = Self::choose_subtree(node, &entry.bbox);
+                path.push(i);
+                node = &mut node.children[i];
+            }
+            touched.insert(path);
+            node.children.push(entry);
+        }
+
+        let (max, min) = (self.max_entries, self.min_entries);
+        let mut extra = Self::settle(&mut self.root, &mut Vec::new(), &touched, max, min, &mut self.stats);
+        while !extra.is_empty() {
+            self.stats.root_splits += 1;
+            let old_root = std::mem::take(&mut self.root);
+            let height = old_root.height + 1;
+            extra.insert(0, old_root);
+            self.root = Entry::new_node(extra);
+            self.root.height = height;
+            extra = Self::split_overfull(&mut self.root, max, min, &mut self.stats);
+        }
+    }
+
+    // Settles the touched nodes below `node` (found at `path`), then `node`
+    // itself. Returns the nodes split off it, which belong beside it.
+    fn settle(
+        node: &mut Entry<T, B>,
+        path: &mut Vec<usize>,
+        touched: &HashSet<Vec<usize>>,
+        max_entries: usize,
+        min_entries: usize,
+        stats: &mut OpStats,
+    ) -> Vec<Entry<T, B>> {
+        let mut extra = Vec::new();
+        for i in 0..node.children.len() {
+            path.push(i);
+            if touched.contains(path) {
+                let child = &mut node.children[i];
+                extra.extend(Self::settle(child, path, touched, max_entries, min_entries, stats));
+            }
+            path.pop();
+        }
+        node.children.extend(extra);
+        node.calc_bbox();
+        Self::split_overfull(node, max_entries, min_entries, stats)
+    }
+
+    // Splits `node` until it and every piece split off it fit in
+    // `max_entries`; the node keeps one piece and the others are returned.
+    fn split_overfull(
+        node: &mut Entry<T, B>,
+        max_entries: usize,
+        min_entries: usize,
+        stats: &mut OpStats,
+    ) -> Vec<Entry<T, B>> {
+        let mut pending = Vec::new();
+        while node.children.len() > max_entries {
+            stats.splits += 1;
+            pending.push(Self::split(node, min_entries));
+        }
+        let mut pieces = Vec::new();
+        while let Some(mut piece) = pending.pop() {
+            while piece.children.len() > max_entries {
+                stats.splits += 1;
+                pending.push(Self::split(&mut piece, min_entries));
+            }
+            pieces.push(piece);
+        }
+        pieces
+    }
+
+    pub(crate) fn _split_root(&mut self, new_node: Entry<T, B>) {
+        self.stats.root_splits += 1;
+        let old_root_children = std::mem::take(&mut self.root.children);
+        let mut old_root = Entry::new_node(old_root_children);
+        old_root.height = self.root.height;
+        old_root.calc_bbox();
+
+        self.root.height += 1;
+        self.root.is_leaf = false;
+        self.root.children = vec![old_root, new_node];
+        self.root.calc_bbox();
+    }
+
+    pub(crate) fn load_entries(&mut self, mut items: Vec<Entry<T, B>>) {
+        if items.len() < self.min_entries {
+            for item in items {
+                self._insert_at_level(item, 0);
+            }
+            return;
+        }
+
+        let len = items.len();
+        let node = self._build(&mut items, 0, len - 1, 0);
+
+        if self.root.children.is_empty() {
+            self.root = node;
+        } else if self.root.height == node.height {
+            self._split_root(node);
+        } else {
+            if self.root.height < node.height {
+                let tmp = std::mem::replace(&mut self.root, node);
+                let level = tmp.height;
+                self._insert_at_level(tmp, level);
+            } else {
+                let level = node.height;
+                self._insert_at_level(node, level);
+            }
+        }
+    }
+
+    fn _build(&self, items: &mut [Entry<T, B>], left: usize, right: usize, height: usize) -> Entry<T, B> {
+        let n = right - left + 1;
+        let mut m = self.max_entries;
+
+        if n <= m {
+            let children = items[left..=right].iter_mut().map(std::mem::take).collect();
+            return Entry::new_node(children);
+        }
+
+        let mut target_height = height;
+        if target_height == 0 {
+            target_height = (n as f64).log(m as f64).ceil() as usize;
+            m = (n as f64 / (m as f64).powi((target_height - 1) as i32)).ceil() as usize;
+        }
+
+        let mut node = Entry::new_node(vec![]);
+        node.height = target_height;
+
+        // Each child gets `ceil(n / m)` items; in 2D the range is cut into
+        // vertical slabs of `ceil(sqrt m)` children, each then cut along y.
+        let child_size = (n as f64 / m as f64).ceil() as usize;
+        let slabs: Vec<usize> = (0..B::AXES)
+            .map(|axis| child_size * ceil_root(m, B::AXES - 1 - axis, B::AXES))
+            .collect();
+        let mut children = vec![];
+        self.build_slabs(items, left, right, &slabs, target_height - 1, &mut children);
+
+        node.children = children;
+        node.calc_bbox();
+        node
+    }
+
+    // Cuts `items[left..=right]` into slabs of `slabs[0]` items along the
+    // first remaining axis, then cuts each slab by the rest of `slabs`; the
+    // last axis yields the children themselves.
+    fn build_slabs(
+        &self,
+        items: &mut [Entry<T, B>],
+        left: usize,
+        right: usize,
+        slabs: &[usize],
+        height: usize,
+        children: &mut Vec<Entry<T, B>>,
+    ) {
+        let axis = B::AXES - slabs.len();
+        let slab = slabs[0];
+        Self::multi_select(items, left, right, slab, axis);
+
+        let mut i = left;
+        while i <= right {
+            let right2 = std::cmp::min(i + slab - 1, right);
+            if slabs.len() == 1 {
+                children.push(self._build(items, i, right2, height));
+            } else {
+                self.build_slabs(items, i, right2, &slabs[1..], height, children);
+            }
+            i += slab;
+        }
+    }
+
+    fn multi_select(arr: &mut [Entry<T, B>], left: usize, right: usize, n: usize, axis: usize) {
+        let mut stack = vec![(left, right)];
+
+        while let Some((l, r)) = stack.pop() {
+            if r - l <= n {
+                continue;
+            }
+
+            let mid = l + ((r - l) as f64 / n as f64 / 2.0).ceil() as usize * n;
+            let target_idx = mid - l;
+            let slice = &mut arr[l..=r];
+
+            slice.select_nth_unstable_by(target_idx, |a, b| a.bbox.cmp_min(&b.bbox, axis));
+
+            stack.push((l, mid));
+            stack.push((mid, r));
+        }
+    }
+
+    fn insert_recursive(
+        node: &mut Entry<T, B>,
+        item: Entry<T, B>,
+        target_level: usize,
+        max_entries: usize,
+        min_entries: usize,
+        stats: &mut OpStats,
+    ) -> Option<Entry<T, B>> {
+        node.extend(&item);
+
+        if node.height - 1 == target_level {
+            node.children.push(item);
+            if node.children.len() > max_entries {
+                stats.splits += 1;
+                return Some(Self::split(node, min_entries));
+            }
+            return None;
+        }
+
+        let best_index = Self::choose_subtree(node, &item.bbox);
+
+        let split_node = Self::insert_recursive(
+            &mut node.children[best_index],
+            item,
+            target_level,
+            max_entries,
+            min_entries,
+            stats,
+        );
+
+        if let Some(new_child) = split_node {
+            node.children.push(new_child);
+            if node.children.len() > max_entries {
+                stats.splits += 1;
+                return Some(Self::split(node, min_entries));
+            }
+        }
+
+        None
+    }
+
+    pub(crate) fn choose_subtree(node: &Entry<T, B>, bbox: &B) -> usize {
+        let mut best_index = 0;
+        let mut min_enlargement = B::Measure::WORST;
+        let mut min_area = B::Measure::WORST;
+        let any_open = node.children.iter().any(|child| !child.frozen);
+
+        for (i, child) in node.children.iter().enumerate() {
+            if child.frozen && any_open {
+                continue;
+            }
+            // Coordinates beyond the safe range overflow the area to infinity
+            // (or NaN); rank those children last instead of NaN-comparing.
+            let area = child.bbox.area().saturate();
+            let enlargement = child.bbox.enlargement(bbox).saturate();
+
+            // Least enlargement, then least area; on a full tie the lowest
+            // index is kept, so the choice depends only on the children.
+            if enlargement < min_enlargement || (enlargement == min_enlargement && area < min_area) {
+                min_enlargement = enlargement;
+                min_area = area;
+                best_index = i;
+            }
+        }
+        best_index
+    }
+
+    fn split(node: &mut Entry<T, B>, min_entries: usize) -> Entry<T, B> {
+        let count = node.children.len();
+
+        Self::choose_split_axis(node, min_entries, count);
+        let split_index = Self::choose_split_index(node, min_entries, count);
+
+        let new_children = node.children.split_off(split_index);
+        let mut new_node = Entry::new_node(new_children);
+        new_node.height = node.height;
+
+        node.calc_bbox();
+        new_node.calc_bbox();
+
+        new_node
+    }
+
+    // Sorts the children along the axis with the least total margin; ties go
+    // to the later axis, which in 2D is JS rbush's "x only if strictly less".
+    fn choose_split_axis(node: &mut Entry<T, B>, m: usize, count: usize) {
+        let mut best_axis = 0;
+        let mut min_margin = B::Measure::WORST;
+        for axis in 0..B::AXES {
+            let margin = Self::all_dist_margin(node, m, count, axis);
+            if margin <= min_margin {
+                min_margin = margin;
+                best_axis = axis;
+            }
+        }
+
+        if best_axis != B::AXES - 1 {
+            node.children
+                .sort_by(|a, b| a.bbox.cmp_min(&b.bbox, best_axis));
+        }
+    }
+
+    fn all_dist_margin(node: &mut Entry<T, B>, m: usize, count: usize, axis: usize) -> B::Measure {
+        node.children
+            .sort_by(|a, b| a.bbox.cmp_min(&b.bbox, axis));
+
+        let mut left_bbox = B::new_empty();
+        let mut right_bbox = B::new_empty();
+
+        for i in 0..m {
+            left_bbox.extend(&node.children[i].bbox);
+        }
+        for i in (count - m)..count {
+            right_bbox.extend(&node.children[i].bbox);
+        }
+
+        let mut margin = left_bbox.margin() + right_bbox.margin();
+
+        for i in m..(count - m) {
+            left_bbox.extend(&node.children[i].bbox);
+            margin = margin + left_bbox.margin();
+        }
+        for i in ((m)..(count - m)).rev() {
+            right_bbox.extend(&node.children[i].bbox);
+            margin = margin + right_bbox.margin();
+        }
+
+        margin
+    }
+
+    fn choose_split_index(node: &Entry<T, B>, m: usize, count: usize) -> usize {
+        let mut min_overlap = B::Measure::WORST;
+        let mut min_area = B::Measure::WORST;
+        let mut index = count - m;
+
+        for i in m..=(count - m) {
+            let mut bbox1 = B::new_empty();
+            let mut bbox2 = B::new_empty();
+
+            for c in &node.children[0..i] {
+                bbox1.extend(&c.bbox);
+            }
+            for c in &node.children[i..count] {
+                bbox2.extend(&c.bbox);
+            }
+
+            let overlap = bbox1.intersection_area(&bbox2);
+            let area = bbox1.area() + bbox2.area();
+
+            if overlap < min_overlap {
+                min_overlap = overlap;
+                index = i;
+                min_area = if area < min_area { area } else { min_area };
+            } else if overlap == min_overlap {
+                if area < min_area {
+                    min_area = area;
+                    index = i;
+                }
+            }
+        }
+        index
+    }
+
+    // Removes the first item under `node` whose bbox lies within `bbox`'s
+    // containing nodes and whose data satisfies `is_item`.
+    pub(crate) fn remove_from_node(
+        node: &mut Entry<T, B>,
+        is_item: &mut impl FnMut(&T) -> bool,
+        bbox: &B,
+        min_entries: usize,
+        reinsert: &mut Vec<Entry<T, B>>,
+    ) -> Option<Entry<T, B>> {
+        let mut found = None;
+        let mut removed = None;
+
+        for (i, child) in node.children.iter_mut().enumerate() {
+            if !child.bbox.contains(bbox) {
+                continue;
+            }
+            if child.is_leaf {
+                if child.data.as_ref().is_some_and(&mut *is_item) {
+                    found = Some(i);
+                    break;
+                }
+            } else if let Some(entry) = Self::remove_from_node(child, is_item, bbox, min_entries, reinsert) {
+                found = Some(i);
+                removed = Some(entry);
+                break;
+            }
+        }
+
+        let idx = found?;
+
+        let child = &node.children[idx];
+        if child.is_leaf {
+            removed = Some(node.children.remove(idx));
+        } else if child.children.len() < min_entries && !child.holds_frozen() {
+            let underflowed_child = node.children.remove(idx);
+            Self::collect_items(underflowed_child, reinsert);
+        }
+        node.calc_bbox();
+        removed
+    }
+
+    pub(crate) fn collect_items(node: Entry<T, B>, acc: &mut Vec<Entry<T, B>>) {
+        for child in node.children {
+            if child.is_leaf {
+                acc.push(child);
+            } else {
+                Self::collect_items(child, acc);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Point {
+        id: usize,
+        x: f64,
+        y: f64,
+    }
+
+    impl BBox for Point {
+        fn bbox(&self) -> Rect {
+            Rect::new(self.x, self.y, self.x, self.y)
+        }
+    }
+
+    fn grid(n: usize) -> Vec<Point> {
+        (0..n * n)
+            .map(|id| Point { id, x: (id % n) as f64, y: (id / n) as f64 })
+            .collect()
+    }
+
+    // Items sit in height-1 nodes only, node heights step down by one, no
+    // node is overfull and every node's bbox is the union of its children's.
+    fn assert_valid(tree: &RBushCore<Point>) {
+        let mut stack = vec![&tree.root];
+        while let Some(node) = stack.pop() {
+            assert!(node.children.len() <= tree.max_entries);
+            let mut bbox = Rect::new_empty();
+            for child in &node.children {
+                bbox.extend(&child.bbox);
+                assert_eq!(child.is_leaf, node.height == 1);
+                if !child.is_leaf {
+                    assert_eq!(child.height, node.height - 1);
+                    stack.push(child);
+                }
+            }
+            if !node.children.is_empty() {
+                assert!(node.bbox == bbox);
+            }
+        }
+    }
+
+    fn ids(items: Vec<&Point>) -> Vec<usize> {
+        let mut ids: Vec<usize> = items.iter().map(|item| item.id).collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn insert_then_search() {
+        let mut tree = RBushCore::new(4);
+        for item in grid(10) {
+            tree.insert(item);
+        }
+        assert_valid(&tree);
+        assert_eq!(tree.len(), 100);
+        assert!(tree.root.height > 1);
+
+        // edges are inclusive
+        let found = ids(tree.search(&Rect::new(2.0, 3.0, 4.0, 3.0)));
+        assert_eq!(found, vec![32, 33, 34]);
+        assert!(tree.search(&Rect::new(10.5, 0.0, 11.0, 9.0)).is_empty());
+        assert!(tree.collides(&Rect::new(2.0, 3.0, 2.0, 3.0)));
+        assert!(!tree.collides(&Rect::new(2.2, 3.2, 2.8, 3.8)));
+    }
+
+    #[test]
+    fn bulk_load_then_search() {
+        let mut tree = RBushCore::new(9);
+        tree.bulk_load(grid(30));
+        assert_valid(&tree);
+        assert_eq!(tree.len(), 900);
+        assert_eq!(tree.all().len(), 900);
+
+        let found = tree.search(&Rect::new(5.0, 5.0, 9.0, 9.0));
+        assert_eq!(found.len(), 25);
+        assert!(found.iter().all(|p| (5.0..=9.0).contains(&p.x) && (5.0..=9.0).contains(&p.y)));
+    }
+
+    #[test]
+    fn bulk_load_merges_into_existing_tree() {
+        let mut small = RBushCore::new(4);
+        small.bulk_load(grid(3));
+        small.bulk_load(grid(20));
+        assert_valid(&small);
+        assert_eq!(small.len(), 409);
+
+        let mut large = RBushCore::new(4);
+        large.bulk_load(grid(20));
+        large.bulk_load(grid(3));
+        assert_valid(&large);
+        assert_eq!(large.len(), 409);
+        assert_eq!(large.search(&Rect::new(0.0, 0.0, 0.0, 0.0)).len(), 2);
+    }
+
+    #[test]
+    fn graft_entries_links_nodes_at_their_height() {
+        let mut tree = RBushCore::new(4);
+        tree.bulk_load(grid(10));
+        let height = tree.root.height;
+
+        // 40 bottom-level nodes of 3 points each, right of the grid
+        let nodes: Vec<Entry<Point>> = (0..40)
+            .map(|n| {
+                let items = (0..3)
+                    .map(|i| {
+                        let point = Point { id: 100 + 3 * n + i, x: 20.0 + n as f64, y: i as f64 };
+                        Entry::leaf_with_bbox(point.bbox(), point)
+                    })
+                    .collect();
+                Entry::new_node(items)
+            })
+            .collect();
+        tree.graft_entries(nodes, 1);
+
+        assert_valid(&tree);
+        assert!(tree.root.height > height);
+        assert_eq!(tree.len(), 220);
+        assert_eq!(ids(tree.search(&Rect::new(20.0, 0.0, 21.0, 2.0))), vec![100, 101, 102, 103, 104, 105]);
+    }
+
+    #[test]
+    fn remove_returns_the_item_and_keeps_the_tree_valid() {
+        let mut tree = RBushCore::new(4);
+        tree.bulk_load(grid(10));
+
+        for id in (0..100).step_by(2) {
+            let item = Point { id, x: (id % 10) as f64, y: (id / 10) as f64 };
+            assert_eq!(tree.remove(&item), Some(item));
+        }
+        assert_valid(&tree);
+        assert_eq!(tree.len(), 50);
+        assert!(ids(tree.all()).iter().all(|id| id % 2 == 1));
+
+        assert_eq!(tree.remove(&Point { id: 0, x: 0.0, y: 0.0 }), None);
+        assert_eq!(tree.remove(&Point { id: 1, x: 5.0, y: 5.0 }), None);
+        assert_eq!(tree.len(), 50);
+    }
+
+    // One-axis box, to run the tree over a `Bounds` other than `Rect`.
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    struct Span(i32, i32);
+
+    impl Bounds for Span {
+        type Measure = i128;
+        const AXES: usize = 1;
+
+        fn new_empty() -> Self {
+            Span(i32::MAX, i32::MIN)
+        }
+
+        fn extend(&mut self, other: &Self) {
+            *self = Span(self.0.min(other.0), self.1.max(other.1));
+        }
+
+        fn intersects(&self, other: &Self) -> bool {
+            other.0 <= self.1 && other.1 >= self.0
+        }
+
+        fn contains(&self, other: &Self) -> bool {
+            self.0 <= other.0 && other.1 <= self.1
+        }
+
+        fn area(&self) -> i128 {
+            (self.1 as i128 - self.0 as i128).max(0)
+        }
+
+        fn margin(&self) -> i128 {
+            self.area()
+        }
+
+        fn enlargement(&self, other: &Self) -> i128 {
+            let mut grown = *self;
+            grown.extend(other);
+            grown.area() - self.area()
+        }
+
+        fn intersection_area(&self, other: &Self) -> i128 {
+            Span(self.0.max(other.0), self.1.min(other.1)).area()
+        }
+
+        fn cmp_min(&self, other: &Self, _axis: usize) -> Ordering {
+            self.0.cmp(&other.0)
+        }
+    }
+
+    impl BBox<Span> for Span {
+        fn bbox(&self) -> Span {
+            *self
+        }
+    }
+
+    #[test]
+    fn runs_over_other_bounds() {
+        let spans: Vec<Span> = (0..300).map(|i| Span(i * 3, i * 3 + 4)).collect();
+        let mut tree = RBushCore::new(4);
+        tree.bulk_load(spans[..150].to_vec());
+        for span in &spans[150..] {
+            tree.insert(*span);
+        }
+        assert_eq!(tree.len(), 300);
+        assert!(tree.root.height > 2);
+
+        let mut found: Vec<i32> = tree.search(&Span(10, 20)).iter().map(|span| span.0).collect();
+        found.sort();
+        assert_eq!(found, vec![6, 9, 12, 15, 18]);
+        assert!(tree.collides(&Span(10, 20)));
+        assert!(!tree.collides(&Span(-5, -1)));
+
+        for span in spans.iter().step_by(2) {
+            assert_eq!(tree.remove(span), Some(*span));
+        }
+        assert_eq!(tree.len(), 150);
+        assert_eq!(tree.search(&Span(10, 20)).len(), 2);
+    }
+
+    #[test]
+    fn removing_everything_empties_the_tree() {
+        let mut tree = RBushCore::new(4);
+        tree.bulk_load(grid(5));
+        for item in grid(5) {
+            assert!(tree.remove(&item).is_some());
+        }
+        assert!(tree.is_empty());
+        assert_eq!(tree.root.height, 1);
+        assert!(tree.search(&Rect::new(0.0, 0.0, 5.0, 5.0)).is_empty());
+    }
+}
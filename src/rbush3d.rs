@@ -0,0 +1,161 @@
+// 3D counterpart of `RBush` for boxes with minX/minY/minZ/maxX/maxY/maxZ.
+// A separate wrapper rather than a mode of `RBush`, so the 2D API and node
+// layout stay untouched; it runs the shared `RBushCore` over `Cube`s and
+// covers the basic insert/load/search/remove set.
+use js_sys::{Array, Reflect};
+use std::cmp::Ordering;
+use wasm_bindgen::prelude::*;
+
+use crate::core::{Bounds, Entry, RBushCore};
+use crate::wasm::to_array;
+
+const AXES: usize = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Cube {
+    min: [f64; AXES],
+    max: [f64; AXES],
+}
+
+impl Cube {
+    fn from_js(item: &JsValue) -> Self {
+        if item.is_null() || item.is_undefined() {
+            return Cube::new_empty();
+        }
+        let get_coord = |prop: &str| {
+            Reflect::get(item, &prop.into())
+                .ok()
+                .and_then(|v| v.as_f64())
+                .unwrap_or(0.0)
+        };
+        Cube {
+            min: [get_coord("minX"), get_coord("minY"), get_coord("minZ")],
+            max: [get_coord("maxX"), get_coord("maxY"), get_coord("maxZ")],
+        }
+    }
+}
+
+impl Bounds for Cube {
+    type Measure = f64;
+    const AXES: usize = AXES;
+
+    fn new_empty() -> Self {
+        Cube {
+            min: [f64::INFINITY; AXES],
+            max: [f64::NEG_INFINITY; AXES],
+        }
+    }
+
+    fn extend(&mut self, other: &Cube) {
+        for a in 0..AXES {
+            self.min[a] = f64::min(self.min[a], other.min[a]);
+            self.max[a] = f64::max(self.max[a], other.max[a]);
+        }
+    }
+
+    fn intersects(&self, other: &Cube) -> bool {
+        (0..AXES).all(|a| other.min[a] <= self.max[a] && other.max[a] >= self.min[a])
+    }
+
+    fn contains(&self, other: &Cube) -> bool {
+        (0..AXES).all(|a| self.min[a] <= other.min[a] && other.max[a] <= self.max[a])
+    }
+
+    // Volume.
+    fn area(&self) -> f64 {
+        (0..AXES).map(|a| self.max[a] - self.min[a]).product()
+    }
+
+    fn margin(&self) -> f64 {
+        (0..AXES).map(|a| self.max[a] - self.min[a]).sum()
+    }
+
+    fn enlargement(&self, other: &Cube) -> f64 {
+        let mut grown = *self;
+        grown.extend(other);
+        grown.area() - self.area()
+    }
+
+    fn intersection_area(&self, other: &Cube) -> f64 {
+        let mut volume = 1.0;
+        for a in 0..AXES {
+            let side = f64::min(self.max[a], other.max[a]) - f64::max(self.min[a], other.min[a]);
+            if side <= 0.0 {
+                return 0.0;
+            }
+            volume *= side;
+        }
+        volume
+    }
+
+    fn cmp_min(&self, other: &Cube, axis: usize) -> Ordering {
+        self.min[axis].total_cmp(&other.min[axis])
+    }
+}
+
+#[wasm_bindgen]
+pub struct RBush3D {
+    core: RBushCore<JsValue, Cube>,
+}
+
+#[wasm_bindgen]
+impl RBush3D {
+    #[wasm_bindgen(constructor)]
+    pub fn new(max_entries: Option<usize>) -> RBush3D {
+        RBush3D {
+            core: RBushCore::new(max_entries.unwrap_or(9)),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.core.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.core.len()
+    }
+
+    #[wasm_bindgen(js_name = isEmpty)]
+    pub fn is_empty(&self) -> bool {
+        self.core.is_empty()
+    }
+
+    pub fn all(&self) -> Array {
+        to_array(self.core.all())
+    }
+
+    pub fn search(&self, bbox_js: &JsValue) -> Array {
+        to_array(self.core.search(&Cube::from_js(bbox_js)))
+    }
+
+    pub fn collides(&self, bbox_js: &JsValue) -> bool {
+        self.core.collides(&Cube::from_js(bbox_js))
+    }
+
+    pub fn insert(&mut self, item: JsValue) {
+        if !item.is_null() && !item.is_undefined() {
+            self.core.stats.inserts += 1;
+            self.core._insert_at_level(Entry::leaf_with_bbox(Cube::from_js(&item), item), 0);
+        }
+    }
+
+    pub fn load(&mut self, data: &Array) {
+        let items: Vec<Entry<JsValue, Cube>> = (0..data.length())
+            .map(|i| data.get(i))
+            .filter(|item| !item.is_null() && !item.is_undefined())
+            .map(|item| Entry::leaf_with_bbox(Cube::from_js(&item), item))
+            .collect();
+        if !items.is_empty() {
+            self.core.stats.inserts += items.len();
+            self.core.load_entries(items);
+        }
+    }
+
+    // Removes the item (matched by reference, located through its own box).
+    pub fn remove(&mut self, item: JsValue) {
+        if item.is_null() || item.is_undefined() {
+            return;
+        }
+        self.core.remove_where(&Cube::from_js(&item), |data| data == &item);
+    }
+}
@@ -0,0 +1,193 @@
+// Integer counterpart of `RBush` for grid-aligned data: coordinates are
+// stored as i32 and areas, margins and enlargements are computed exactly in
+// wider integers, so the split and choose-subtree heuristics never see float
+// rounding. Like `RBush3D`, it runs the shared `RBushCore` and covers the
+// basic insert/load/search/remove set.
+use js_sys::{Array, Reflect};
+use std::cmp::Ordering;
+use wasm_bindgen::prelude::*;
+
+use crate::core::{Bounds, Entry, RBushCore};
+use crate::wasm::to_array;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct IRect {
+    min_x: i32,
+    min_y: i32,
+    max_x: i32,
+    max_y: i32,
+}
+
+impl IRect {
+    // Fractional coordinates are widened to the enclosing integer box
+    // (floor for mins, ceil for maxes); out-of-range values saturate. A
+    // missing, non-numeric or NaN field is an error, as it has no integer
+    // to round to, and so is an inverted axis, whose negative side lengths
+    // would throw off every area-based heuristic.
+    fn from_js(item: &JsValue) -> Result<Self, JsError> {
+        let get_coord = |prop: &str| {
+            Reflect::get(item, &prop.into())
+                .ok()
+                .and_then(|v| v.as_f64())
+                .filter(|v| !v.is_nan())
+                .ok_or_else(|| JsError::new(&format!("bbox field `{}` is missing or not a number", prop)))
+        };
+        let (min_x, min_y) = (get_coord("minX")?, get_coord("minY")?);
+        let (max_x, max_y) = (get_coord("maxX")?, get_coord("maxY")?);
+        if max_x < min_x {
+            return Err(JsError::new(&format!("bbox maxX ({}) is less than minX ({})", max_x, min_x)));
+        }
+        if max_y < min_y {
+            return Err(JsError::new(&format!("bbox maxY ({}) is less than minY ({})", max_y, min_y)));
+        }
+        Ok(IRect {
+            min_x: min_x.floor() as i32,
+            min_y: min_y.floor() as i32,
+            max_x: max_x.ceil() as i32,
+            max_y: max_y.ceil() as i32,
+        })
+    }
+
+    // Side lengths as i64, which can't overflow for any pair of i32s.
+    fn sides(&self) -> (i64, i64) {
+        (
+            self.max_x as i64 - self.min_x as i64,
+            self.max_y as i64 - self.min_y as i64,
+        )
+    }
+}
+
+impl Bounds for IRect {
+    // i128, since the product of two i64 sides can exceed i64.
+    type Measure = i128;
+    const AXES: usize = 2;
+
+    fn new_empty() -> Self {
+        IRect {
+            min_x: i32::MAX,
+            min_y: i32::MAX,
+            max_x: i32::MIN,
+            max_y: i32::MIN,
+        }
+    }
+
+    fn extend(&mut self, other: &IRect) {
+        self.min_x = self.min_x.min(other.min_x);
+        self.min_y = self.min_y.min(other.min_y);
+        self.max_x = self.max_x.max(other.max_x);
+        self.max_y = self.max_y.max(other.max_y);
+    }
+
+    fn intersects(&self, other: &IRect) -> bool {
+        other.min_x <= self.max_x && other.min_y <= self.max_y && other.max_x >= self.min_x && other.max_y >= self.min_y
+    }
+
+    fn contains(&self, other: &IRect) -> bool {
+        self.min_x <= other.min_x && self.min_y <= other.min_y && other.max_x <= self.max_x && other.max_y <= self.max_y
+    }
+
+    fn area(&self) -> i128 {
+        let (w, h) = self.sides();
+        w as i128 * h as i128
+    }
+
+    fn margin(&self) -> i128 {
+        let (w, h) = self.sides();
+        (w + h) as i128
+    }
+
+    fn enlargement(&self, other: &IRect) -> i128 {
+        let mut grown = *self;
+        grown.extend(other);
+        grown.area() - self.area()
+    }
+
+    fn intersection_area(&self, other: &IRect) -> i128 {
+        let w = i64::from(self.max_x.min(other.max_x)) - i64::from(self.min_x.max(other.min_x));
+        let h = i64::from(self.max_y.min(other.max_y)) - i64::from(self.min_y.max(other.min_y));
+        if w <= 0 || h <= 0 {
+            return 0;
+        }
+        w as i128 * h as i128
+    }
+
+    fn cmp_min(&self, other: &IRect, axis: usize) -> Ordering {
+        match axis {
+            0 => self.min_x.cmp(&other.min_x),
+            _ => self.min_y.cmp(&other.min_y),
+        }
+    }
+}
+
+#[wasm_bindgen]
+pub struct RBushI32 {
+    core: RBushCore<JsValue, IRect>,
+}
+
+#[wasm_bindgen]
+impl RBushI32 {
+    #[wasm_bindgen(constructor)]
+    pub fn new(max_entries: Option<usize>) -> RBushI32 {
+        RBushI32 {
+            core: RBushCore::new(max_entries.unwrap_or(9)),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.core.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.core.len()
+    }
+
+    #[wasm_bindgen(js_name = isEmpty)]
+    pub fn is_empty(&self) -> bool {
+        self.core.is_empty()
+    }
+
+    pub fn all(&self) -> Array {
+        to_array(self.core.all())
+    }
+
+    pub fn search(&self, bbox_js: &JsValue) -> Result<Array, JsError> {
+        Ok(to_array(self.core.search(&IRect::from_js(bbox_js)?)))
+    }
+
+    pub fn collides(&self, bbox_js: &JsValue) -> Result<bool, JsError> {
+        Ok(self.core.collides(&IRect::from_js(bbox_js)?))
+    }
+
+    pub fn insert(&mut self, item: JsValue) -> Result<(), JsError> {
+        if !item.is_null() && !item.is_undefined() {
+            let entry = Entry::leaf_with_bbox(IRect::from_js(&item)?, item);
+            self.core.stats.inserts += 1;
+            self.core._insert_at_level(entry, 0);
+        }
+        Ok(())
+    }
+
+    // Rejects the whole batch, inserting nothing, if any item has a bad box.
+    pub fn load(&mut self, data: &Array) -> Result<(), JsError> {
+        let items: Vec<Entry<JsValue, IRect>> = (0..data.length())
+            .map(|i| data.get(i))
+            .filter(|item| !item.is_null() && !item.is_undefined())
+            .map(|item| IRect::from_js(&item).map(|bbox| Entry::leaf_with_bbox(bbox, item)))
+            .collect::<Result<_, _>>()?;
+        if !items.is_empty() {
+            self.core.stats.inserts += items.len();
+            self.core.load_entries(items);
+        }
+        Ok(())
+    }
+
+    // Removes the item (matched by reference, located through its own box).
+    pub fn remove(&mut self, item: JsValue) -> Result<(), JsError> {
+        if item.is_null() || item.is_undefined() {
+            return Ok(());
+        }
+        let bbox = IRect::from_js(&item)?;
+        self.core.remove_where(&bbox, |data| data == &item);
+        Ok(())
+    }
+}
@@ -0,0 +1,3642 @@
+use js_sys::{Array, Float64Array, Function, Object, Reflect, Uint8Array, Uint32Array};
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+use wasm_bindgen::prelude::*;
+
+use crate::core::{BBox, OpStats, RBushCore, Rect};
+
+// Size of one `search_buffer` record: four f64 coordinates, a u32 data
+// index and 4 bytes of padding.
+const SEARCH_RECORD_BYTES: usize = 40;
+
+// `to_bytes` header: magic, then a little-endian u32 format version and u32
+// item count. Bump the version whenever the layout below it changes.
+const BYTES_MAGIC: &[u8; 4] = b"RBSH";
+const BYTES_VERSION: u32 = 1;
+const BYTES_HEADER_LEN: usize = 12;
+// Height cap for decoded trees (`fromBytes`, `fromJSON`, `insertLevel`).
+// Real trees stay far below it; it keeps corrupt input from recursing
+// arbitrarily deep.
+const MAX_TREE_HEIGHT: usize = 64;
+
+impl Rect {
+    fn from_js(item: &JsValue) -> Self {
+        if item.is_null() || item.is_undefined() {
+            return Rect::new_empty();
+        }
+
+        
+        let get_coord = |prop: &str| {
+            Reflect::get(item, &prop.into())
+                .ok() 
+                .and_then(|v| v.as_f64()) 
+                .unwrap_or(0.0) 
+        };
+
+        Rect {
+            min_x: get_coord("minX"),
+            min_y: get_coord("minY"),
+            max_x: get_coord("maxX"),
+            max_y: get_coord("maxY"),
+        }
+    }
+
+    // Like `from_js`, but fails on a missing or non-numeric field or on an
+    // inverted axis instead of reading it as 0.
+    fn from_js_strict(item: &JsValue) -> Result<Self, JsError> {
+        let get_coord = |prop: &str| {
+            Reflect::get(item, &prop.into())
+                .ok()
+                .and_then(|v| v.as_f64())
+                .filter(|v| !v.is_nan())
+                .ok_or_else(|| JsError::new(&format!("bbox field `{}` is missing or not a number", prop)))
+        };
+        let rect = Rect {
+            min_x: get_coord("minX")?,
+            min_y: get_coord("minY")?,
+            max_x: get_coord("maxX")?,
+            max_y: get_coord("maxY")?,
+        };
+        if rect.max_x < rect.min_x {
+            return Err(JsError::new(&format!("bbox maxX ({}) is less than minX ({})", rect.max_x, rect.min_x)));
+        }
+        if rect.max_y < rect.min_y {
+            return Err(JsError::new(&format!("bbox maxY ({}) is less than minY ({})", rect.max_y, rect.min_y)));
+        }
+        Ok(rect)
+    }
+
+    // Bbox to locate a stored item by when removing it. Payloads stored with
+    // `insertWithBox` may lack bbox fields; every node contains the empty
+    // box, so those are looked for across the whole tree.
+    fn lookup_js(item: &JsValue) -> Self {
+        if Reflect::has(item, &"minX".into()).unwrap_or(false) {
+            Rect::from_js(item)
+        } else {
+            Rect::new_empty()
+        }
+    }
+
+    fn to_js(self) -> Object {
+        let obj = Object::new();
+        let _ = Reflect::set(&obj, &"minX".into(), &self.min_x.into());
+        let _ = Reflect::set(&obj, &"minY".into(), &self.min_y.into());
+        let _ = Reflect::set(&obj, &"maxX".into(), &self.max_x.into());
+        let _ = Reflect::set(&obj, &"maxY".into(), &self.max_y.into());
+        obj
+    }
+}
+
+impl BBox for JsValue {
+    fn bbox(&self) -> Rect {
+        Rect::from_js(self)
+    }
+}
+
+// Point-to-rectangle metric used by the nearest-neighbor queries.
+#[derive(Clone, Copy, PartialEq)]
+enum Metric {
+    Euclidean,
+    Manhattan,
+    Chebyshev,
+}
+
+impl Metric {
+    fn parse(name: &str) -> Result<Metric, JsError> {
+        match name {
+            "euclidean" => Ok(Metric::Euclidean),
+            "manhattan" => Ok(Metric::Manhattan),
+            "chebyshev" => Ok(Metric::Chebyshev),
+            _ => Err(JsError::new(&format!(
+                "unknown metric {name:?}, expected \"euclidean\", \"manhattan\" or \"chebyshev\""
+            ))),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Metric::Euclidean => "euclidean",
+            Metric::Manhattan => "manhattan",
+            Metric::Chebyshev => "chebyshev",
+        }
+    }
+
+    // Orders like the distance from the point to the rectangle's nearest
+    // point; for Euclidean it is the squared distance, to skip the sqrt on
+    // the hot path. A node never ranks above anything inside it, so it is a
+    // valid pruning bound for every metric.
+    fn rank(self, rect: &Rect, x: f64, y: f64) -> f64 {
+        let (dx, dy) = rect.point_gap(x, y);
+        match self {
+            Metric::Euclidean => dx * dx + dy * dy,
+            Metric::Manhattan => dx + dy,
+            Metric::Chebyshev => f64::max(dx, dy),
+        }
+    }
+
+    // The actual distance behind a `rank` value.
+    fn distance(self, rank: f64) -> f64 {
+        match self {
+            Metric::Euclidean => rank.sqrt(),
+            Metric::Manhattan | Metric::Chebyshev => rank,
+        }
+    }
+
+    // The `rank` value of a distance, to compare against without converting
+    // every rank back. Negative distances map below every rank.
+    fn rank_of(self, distance: f64) -> f64 {
+        if distance < 0.0 {
+            return f64::NEG_INFINITY;
+        }
+        match self {
+            Metric::Euclidean => distance * distance,
+            Metric::Manhattan | Metric::Chebyshev => distance,
+        }
+    }
+}
+
+// Items returned by an `RBushCore` query, as a JS array. Shared by the
+// `RBush3D` and `RBushI32` wrappers.
+pub(crate) fn to_array(items: Vec<&JsValue>) -> Array {
+    let result = Array::new();
+    for item in items {
+        result.push(item);
+    }
+    result
+}
+
+// Euclidean distance from the segment a-b to the rectangle, 0 when they
+// touch. A segment that misses the rectangle comes closest to it at one of
+// its endpoints or at one of the rectangle's corners, so the distance is the
+// smallest of those six point distances.
+fn segment_box_dist(a: (f64, f64), b: (f64, f64), rect: &Rect) -> f64 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+
+    // Liang-Barsky clip of the segment against the rectangle
+    let (mut t0, mut t1) = (0.0_f64, 1.0_f64);
+    let mut hits = true;
+    for (p, q) in [
+        (-dx, a.0 - rect.min_x),
+        (dx, rect.max_x - a.0),
+        (-dy, a.1 - rect.min_y),
+        (dy, rect.max_y - a.1),
+    ] {
+        if p == 0.0 {
+            hits &= q >= 0.0;
+        } else if p < 0.0 {
+            t0 = t0.max(q / p);
+        } else {
+            t1 = t1.min(q / p);
+        }
+    }
+    if hits && t0 <= t1 {
+        return 0.0;
+    }
+
+    let len_sq = dx * dx + dy * dy;
+    let to_segment = |x: f64, y: f64| {
+        let t = if len_sq > 0.0 {
+            (((x - a.0) * dx + (y - a.1) * dy) / len_sq).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        (a.0 + t * dx - x).hypot(a.1 + t * dy - y)
+    };
+    let to_box = |(x, y): (f64, f64)| {
+        let (gx, gy) = rect.point_gap(x, y);
+        gx.hypot(gy)
+    };
+
+    [
+        to_box(a),
+        to_box(b),
+        to_segment(rect.min_x, rect.min_y),
+        to_segment(rect.min_x, rect.max_y),
+        to_segment(rect.max_x, rect.min_y),
+        to_segment(rect.max_x, rect.max_y),
+    ]
+    .into_iter()
+    .fold(f64::INFINITY, f64::min)
+}
+
+// Andrew's monotone chain. Returns the hull counter-clockwise without
+// repeating the first point; fewer than three distinct points are returned
+// as-is (sorted, deduplicated).
+fn convex_hull(mut points: Vec<(f64, f64)>) -> Vec<(f64, f64)> {
+    points.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)));
+    points.dedup();
+    if points.len() < 3 {
+        return points;
+    }
+
+    let cross = |o: (f64, f64), a: (f64, f64), b: (f64, f64)| {
+        (a.0 - o.0) * (b.1 - o.1) - (a.1 - o.1) * (b.0 - o.0)
+    };
+    let mut hull: Vec<(f64, f64)> = Vec::with_capacity(points.len() + 1);
+    for &p in &points {
+        while hull.len() >= 2 && cross(hull[hull.len() - 2], hull[hull.len() - 1], p) <= 0.0 {
+            hull.pop();
+        }
+        hull.push(p);
+    }
+    let lower_len = hull.len() + 1;
+    for &p in points.iter().rev().skip(1) {
+        while hull.len() >= lower_len && cross(hull[hull.len() - 2], hull[hull.len() - 1], p) <= 0.0 {
+            hull.pop();
+        }
+        hull.push(p);
+    }
+    hull.pop();
+    hull
+}
+
+// Smallest circle enclosing all points as (cx, cy, radius), by Welzl's
+// algorithm in its iterative form. The points are shuffled first (with a
+// fixed-seed xorshift, so results are reproducible), which makes the
+// expected running time linear. None for no points.
+fn enclosing_circle(mut points: Vec<(f64, f64)>) -> Option<(f64, f64, f64)> {
+    points.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)));
+    points.dedup();
+    let mut state = 0x9e37_79b9_7f4a_7c15_u64;
+    for i in (1..points.len()).rev() {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        points.swap(i, (state % (i as u64 + 1)) as usize);
+    }
+
+    let inside = |c: (f64, f64, f64), p: (f64, f64)| {
+        (p.0 - c.0).hypot(p.1 - c.1) <= c.2 * (1.0 + 1e-12) + 1e-12
+    };
+    let diameter = |a: (f64, f64), b: (f64, f64)| {
+        ((a.0 + b.0) / 2.0, (a.1 + b.1) / 2.0, (a.0 - b.0).hypot(a.1 - b.1) / 2.0)
+    };
+    let circumcircle = |a: (f64, f64), b: (f64, f64), c: (f64, f64)| {
+        let (bx, by) = (b.0 - a.0, b.1 - a.1);
+        let (cx, cy) = (c.0 - a.0, c.1 - a.1);
+        let d = 2.0 * (bx * cy - by * cx);
+        if d == 0.0 {
+            // collinear: the two farthest points span the circle
+            return [diameter(a, b), diameter(a, c), diameter(b, c)]
+                .into_iter()
+                .max_by(|p, q| p.2.total_cmp(&q.2))
+                .unwrap();
+        }
+        let ux = (cy * (bx * bx + by * by) - by * (cx * cx + cy * cy)) / d;
+        let uy = (bx * (cx * cx + cy * cy) - cx * (bx * bx + by * by)) / d;
+        (a.0 + ux, a.1 + uy, ux.hypot(uy))
+    };
+
+    let first = *points.first()?;
+    let mut circle = (first.0, first.1, 0.0);
+    for i in 1..points.len() {
+        if inside(circle, points[i]) {
+            continue;
+        }
+        circle = (points[i].0, points[i].1, 0.0);
+        for j in 0..i {
+            if inside(circle, points[j]) {
+                continue;
+            }
+            circle = diameter(points[i], points[j]);
+            for k in 0..j {
+                if !inside(circle, points[k]) {
+                    circle = circumcircle(points[i], points[j], points[k]);
+                }
+            }
+        }
+    }
+    Some(circle)
+}
+
+// Best-first queue element; `BinaryHeap` is a max-heap, so the ordering is
+// reversed to pop the closest entry first.
+struct Candidate<'a> {
+    dist: f64,
+    entry: &'a Entry,
+}
+
+impl PartialEq for Candidate<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.dist.total_cmp(&other.dist) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate<'_> {}
+
+impl PartialOrd for Candidate<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.dist.total_cmp(&self.dist)
+    }
+}
+
+type Entry = crate::core::Entry<JsValue>;
+
+impl Entry {
+    // Node in the JS rbush `toJSON` layout, keys in the same order: nodes
+    // of height 1 are `leaf: true` and list the item data itself.
+    fn to_js_object(&self) -> JsValue {
+        let obj = Object::new();
+        let leaf = self.height == 1;
+
+        let js_children = Array::new();
+        for child in &self.children {
+            js_children.push(&if leaf {
+                child.data().clone()
+            } else {
+                child.to_js_object()
+            });
+        }
+        let _ = Reflect::set(&obj, &"children".into(), &js_children.into());
+        let _ = Reflect::set(&obj, &"height".into(), &(self.height as f64).into());
+        let _ = Reflect::set(&obj, &"leaf".into(), &leaf.into());
+        let _ = Reflect::set(&obj, &"minX".into(), &self.bbox.min_x.into());
+        let _ = Reflect::set(&obj, &"minY".into(), &self.bbox.min_y.into());
+        let _ = Reflect::set(&obj, &"maxX".into(), &self.bbox.max_x.into());
+        let _ = Reflect::set(&obj, &"maxY".into(), &self.bbox.max_y.into());
+        obj.into()
+    }
+
+    fn to_skeleton_object(&self) -> JsValue {
+        let obj = self.bbox.to_js();
+        let _ = Reflect::set(&obj, &"height".into(), &(self.height as f64).into());
+        let _ = Reflect::set(
+            &obj,
+            &"childCount".into(),
+            &(self.children.len() as f64).into(),
+        );
+
+        let js_children = Array::new();
+        for child in &self.children {
+            if !child.is_leaf {
+                js_children.push(&child.to_skeleton_object());
+            }
+        }
+        let _ = Reflect::set(&obj, &"children".into(), &js_children.into());
+        obj.into()
+    }
+
+    // `read` gives each item's bbox; node bboxes are recomputed from their
+    // children. `height` is the height the parent expects (None for a top
+    // node), so every child must sit exactly one level below its parent and
+    // serialized leaf nodes only at height 1, as `read_node` checks for
+    // `fromBytes`.
+    fn from_js_object(
+        val: &JsValue,
+        read: &impl Fn(&JsValue) -> Result<Rect, JsError>,
+        height: Option<usize>,
+    ) -> Result<Self, JsError> {
+        let bbox = Rect::from_js(val);
+        let is_leaf = Reflect::get(val, &"leaf".into())
+            .ok()
+            .and_then(|v| v.as_bool())
+            .unwrap_or(true);
+        let node_height = match Reflect::get(val, &"height".into()).ok().and_then(|v| v.as_f64()) {
+            Some(h) if h.fract() == 0.0 && h >= 1.0 && h <= MAX_TREE_HEIGHT as f64 => h as usize,
+            Some(_) => return Err(JsError::new("JSON tree has inconsistent node heights")),
+            None if is_leaf => 1,
+            None => height.ok_or_else(|| JsError::new("JSON tree node is missing its height"))?,
+        };
+        if (is_leaf != (node_height == 1)) || height.is_some_and(|h| h != node_height) {
+            return Err(JsError::new("JSON tree has inconsistent node heights"));
+        }
+        let js_children = Reflect::get(val, &"children".into())
+            .ok()
+            .and_then(|v| v.dyn_into::<Array>().ok())
+            .unwrap_or_else(Array::new);
+
+        let mut children = Vec::with_capacity(js_children.length() as usize);
+        if is_leaf {
+            // null and undefined items are dropped, as `load` drops them
+            for item in js_children.iter().filter(|item| !item.is_null() && !item.is_undefined()) {
+                children.push(Entry::leaf_with_bbox(read(&item)?, item));
+            }
+        } else {
+            for i in 0..js_children.length() {
+                children.push(Entry::from_js_object(&js_children.get(i), read, Some(node_height - 1))?);
+            }
+        }
+
+        // A serialized leaf node holds the items themselves; in memory that is
+        // a regular node of height 1 whose children are item entries.
+        let mut node = Entry {
+            bbox,
+            data: None,
+            is_leaf: false,
+            height: node_height,
+            children,
+            time: f64::NEG_INFINITY,
+            pin: 0,
+            tags: 0,
+            count: 0,
+            extent: Rect::new_empty(),
+            pad: 0.0,
+            frozen: false,
+        };
+        if !node.children.is_empty() {
+            node.calc_bbox();
+        }
+        Ok(node)
+    }
+
+    // Copy of the subtree with every item's data replaced by null and pins
+    // dropped.
+    fn clone_structure(&self) -> Entry {
+        Entry {
+            data: self.data.as_ref().map(|_| JsValue::NULL),
+            pin: 0,
+            children: self.children.iter().map(Entry::clone_structure).collect(),
+            ..*self
+        }
+    }
+
+    // Item data of a leaf entry.
+    fn data(&self) -> &JsValue {
+        self.data.as_ref().expect("item entries hold data")
+    }
+}
+
+// Source of `RBush::id`.
+static NEXT_TREE_ID: AtomicU64 = AtomicU64::new(0);
+
+#[wasm_bindgen]
+pub struct RBush {
+    // Unique per tree (copies included), so a `SearchCursor` can tell the
+    // tree it came from.
+    id: u64,
+    core: RBushCore<JsValue>,
+    snapshots: HashMap<u64, Entry>,
+    next_snapshot: u64,
+    // Removals since the last compaction; `optimize()` runs automatically once
+    // this reaches `auto_compact_after` (0 disables auto compaction).
+    pending_removals: usize,
+    auto_compact_after: usize,
+    // Last known bbox of each pinned leaf, used to find it again after the
+    // tree reorganizes.
+    pins: HashMap<u64, Rect>,
+    next_pin: u64,
+    metric: Metric,
+    // Items buffered by `push_load_chunk`, waiting for `finish_load`.
+    pending_load: Vec<Entry>,
+    // Number of nodes marked by `freeze_region`.
+    frozen_nodes: usize,
+    // Set by `set_strict`: `read_bbox` rejects malformed boxes.
+    strict: bool,
+}
+
+#[wasm_bindgen]
+impl RBush {
+    #[wasm_bindgen(constructor)]
+    pub fn new(max_entries: Option<usize>, auto_compact_after: Option<usize>, fill_factor: Option<f64>) -> RBush {
+        let max_entries = max_entries.unwrap_or(9);
+        RBush {
+            id: NEXT_TREE_ID.fetch_add(1, AtomicOrdering::Relaxed),
+            core: match fill_factor {
+                Some(fill) => RBushCore::with_fill_factor(max_entries, fill),
+                None => RBushCore::new(max_entries),
+            },
+            snapshots: HashMap::new(),
+            next_snapshot: 0,
+            pending_removals: 0,
+            auto_compact_after: auto_compact_after.unwrap_or(0),
+            pins: HashMap::new(),
+            next_pin: 1,
+            metric: Metric::Euclidean,
+            pending_load: Vec::new(),
+            frozen_nodes: 0,
+            strict: false,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.core.clear();
+        self.pending_removals = 0;
+        self.pins.clear();
+        self.pending_load.clear();
+        self.frozen_nodes = 0;
+    }
+
+    // Number of items, O(1): every node keeps its subtree's item count.
+    pub fn len(&self) -> usize {
+        self.core.len()
+    }
+
+    #[wasm_bindgen(js_name = isEmpty)]
+    pub fn is_empty(&self) -> bool {
+        self.core.is_empty()
+    }
+
+    // Node capacity the tree was built with, after clamping to at least 4.
+    #[wasm_bindgen(getter = maxEntries)]
+    pub fn max_entries(&self) -> usize {
+        self.core.max_entries
+    }
+
+    // Fill below which a non-root node is dissolved on removal.
+    #[wasm_bindgen(getter = minEntries)]
+    pub fn min_entries(&self) -> usize {
+        self.core.min_entries
+    }
+
+    // Number of node levels; 1 for an empty tree or one whose root holds
+    // the items directly.
+    pub fn height(&self) -> usize {
+        self.core.root.height
+    }
+
+    // Number of nodes, the root and leaf nodes included (items are not
+    // nodes).
+    #[wasm_bindgen(js_name = nodeCount)]
+    pub fn node_count(&self) -> usize {
+        let mut count = 0;
+        let mut stack = vec![&self.core.root];
+        while let Some(node) = stack.pop() {
+            count += 1;
+            stack.extend(node.children.iter().filter(|child| !child.is_leaf));
+        }
+        count
+    }
+
+    // Number of leaf nodes (nodes holding items) at each depth, the root
+    // being depth 0. A balanced tree has all of them at depth `height - 1`.
+    #[wasm_bindgen(js_name = depthHistogram)]
+    pub fn depth_histogram(&self) -> Array {
+        let mut counts: Vec<usize> = Vec::new();
+        let mut stack = vec![(&self.core.root, 0)];
+        while let Some((node, depth)) = stack.pop() {
+            if node.height == 1 {
+                if counts.len() <= depth {
+                    counts.resize(depth + 1, 0);
+                }
+                counts[depth] += 1;
+            } else {
+                stack.extend(node.children.iter().map(|child| (child, depth + 1)));
+            }
+        }
+        let result = Array::new();
+        for count in counts {
+            result.push(&(count as f64).into());
+        }
+        result
+    }
+
+    // Shape metrics from one traversal: leaf and internal node counts, the
+    // average number of children per node, the height, and the overlap area
+    // summed over every pair of sibling nodes (item overlap is left out).
+    pub fn stats(&self) -> JsValue {
+        let mut leaf_nodes = 0;
+        let mut internal_nodes = 0;
+        let mut children = 0;
+        let mut overlap = 0.0;
+        let mut stack = vec![&self.core.root];
+        while let Some(node) = stack.pop() {
+            children += node.children.len();
+            if node.height == 1 {
+                leaf_nodes += 1;
+                continue;
+            }
+            internal_nodes += 1;
+            for (i, a) in node.children.iter().enumerate() {
+                for b in &node.children[i + 1..] {
+                    overlap += a.bbox.intersection_area(&b.bbox);
+                }
+            }
+            stack.extend(node.children.iter());
+        }
+        let nodes = leaf_nodes + internal_nodes;
+
+        let obj = Object::new();
+        let _ = Reflect::set(&obj, &"leafNodes".into(), &(leaf_nodes as f64).into());
+        let _ = Reflect::set(&obj, &"internalNodes".into(), &(internal_nodes as f64).into());
+        let _ = Reflect::set(&obj, &"avgChildren".into(), &(children as f64 / nodes as f64).into());
+        let _ = Reflect::set(&obj, &"height".into(), &(self.core.root.height as f64).into());
+        let _ = Reflect::set(&obj, &"overlapArea".into(), &overlap.into());
+        obj.into()
+    }
+
+    pub fn all(&self) -> Array {
+        let result = Array::new();
+        self._all(&self.core.root, &result);
+        result
+    }
+
+    // Every item's box as flat `[minX, minY, maxX, maxY, ...]`, in the same
+    // order as `all()`, so box `i` belongs to `all()[i]`.
+    #[wasm_bindgen(js_name = allBoxes)]
+    pub fn all_boxes(&self) -> Vec<f64> {
+        let mut boxes = Vec::with_capacity(self.core.len() * 4);
+        let mut stack = vec![&self.core.root];
+        while let Some(node) = stack.pop() {
+            for child in &node.children {
+                if !child.is_leaf {
+                    stack.push(child);
+                } else {
+                    let e = child.shape();
+                    boxes.extend_from_slice(&[e.min_x, e.min_y, e.max_x, e.max_y]);
+                }
+            }
+        }
+        boxes
+    }
+
+    // `all` in left-to-right order: each node's children in stored order,
+    // depth first. Repeatable for a given tree layout, unlike `all`.
+    #[wasm_bindgen(js_name = allOrdered)]
+    pub fn all_ordered(&self) -> Array {
+        let result = Array::new();
+        let mut stack = vec![&self.core.root];
+        while let Some(node) = stack.pop() {
+            if !node.is_leaf {
+                stack.extend(node.children.iter().rev());
+                continue;
+            }
+            let data = node.data();
+            result.push(data);
+        }
+        result
+    }
+
+    // Splits the items into `n` arrays whose sizes differ by at most one.
+    // The cuts follow the tree's depth-first item order, so each chunk is a
+    // run of neighbouring subtrees and stays spatially coherent. With more
+    // chunks than items, some chunks are empty.
+    #[wasm_bindgen(js_name = partitionItems)]
+    pub fn partition_items(&self, n: usize) -> Array {
+        let chunks = Array::new();
+        let items = self.all();
+        let total = items.length() as usize;
+        for i in 0..n {
+            let start = i * total / n;
+            let end = (i + 1) * total / n;
+            chunks.push(&items.slice(start as u32, end as u32));
+        }
+        chunks
+    }
+
+    pub fn search(&self, bbox_js: &JsValue) -> Result<Array, JsError> {
+        Ok(self.search_in(&self.core.root, &self.read_bbox(bbox_js)?))
+    }
+
+    // Items lying entirely inside the box, rather than merely intersecting
+    // it. A node the box contains is taken whole, as in `search`.
+    #[wasm_bindgen(js_name = searchContained)]
+    pub fn search_contained(&self, bbox_js: &JsValue) -> Result<Array, JsError> {
+        let bbox = self.read_bbox(bbox_js)?;
+        let result = Array::new();
+        let mut stack = vec![&self.core.root];
+
+        while let Some(node) = stack.pop() {
+            if !bbox.intersects(&node.bbox) {
+                continue;
+            }
+
+            for child in &node.children {
+                if !bbox.intersects(child.shape()) {
+                    continue;
+                }
+                if child.is_leaf {
+                    if bbox.contains(child.shape()) {
+                        result.push(child.data());
+                    }
+                } else if bbox.contains(child.shape()) {
+                    self._all(child, &result);
+                } else {
+                    stack.push(child);
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    // `search` with the box given as `[minX, minY, maxX, maxY]`, typically a
+    // reused Float64Array, which skips the per-query property lookups.
+    #[wasm_bindgen(js_name = searchFlat)]
+    pub fn search_flat(&self, bbox: &[f64]) -> Result<Array, JsError> {
+        if bbox.len() != 4 {
+            return Err(JsError::new(&format!("expected 4 coordinates, got {}", bbox.len())));
+        }
+        Ok(self.search_in(&self.core.root, &Rect::from_flat(bbox)))
+    }
+
+    // Cursor over the matches of `search`, for result sets too large to
+    // return as one array; see `SearchCursor::next_batch`.
+    #[wasm_bindgen(js_name = searchCursor)]
+    pub fn search_cursor(&self, bbox_js: &JsValue) -> Result<SearchCursor, JsError> {
+        let bbox = self.read_bbox(bbox_js)?;
+        Ok(SearchCursor {
+            tree: self.id,
+            bbox,
+            stack: if bbox.intersects(&self.core.root.bbox) { vec![vec![]] } else { vec![] },
+            pending: Vec::new(),
+        })
+    }
+
+    // Items whose bbox contains the point, edges included: a search with a
+    // zero-size box, without passing a bbox object in from JS.
+    #[wasm_bindgen(js_name = searchPoint)]
+    pub fn search_point(&self, x: f64, y: f64) -> Array {
+        self.search_in(&self.core.root, &Rect::new(x, y, x, y))
+    }
+
+    // Whether any item's bbox contains the point, edges included; `collides`
+    // for a point, stopping at the first hit.
+    #[wasm_bindgen(js_name = coversPoint)]
+    pub fn covers_point(&self, x: f64, y: f64) -> bool {
+        self.point_covered(x, y)
+    }
+
+    fn search_in(&self, root: &Entry, bbox: &Rect) -> Array {
+        let result = Array::new();
+        let mut stack = vec![root];
+
+        while let Some(node) = stack.pop() {
+            if !bbox.intersects(&node.bbox) {
+                continue;
+            }
+
+            for child in &node.children {
+                if bbox.intersects(child.shape()) {
+                    if child.is_leaf {
+                        result.push(child.data());
+                    } else if bbox.contains(child.shape()) {
+                        self._all(child, &result);
+                    } else {
+                        stack.push(child);
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    // Level-of-detail search: items come back as their data, but a subtree
+    // that lies entirely inside the box and holds more than `max_items`
+    // items comes back as one {minX, minY, maxX, maxY, count} summary
+    // instead of being descended into. A dense node straddling the box edge
+    // is descended into rather than summarized, since its count would
+    // include items outside the box: its children inside the box may still
+    // be summarized, and the rest yield their matching items, so summary
+    // counts plus items always add up to what `search` finds.
+    #[wasm_bindgen(js_name = searchAdaptive)]
+    pub fn search_adaptive(&self, bbox_js: &JsValue, max_items: usize) -> Result<Array, JsError> {
+        let bbox = self.read_bbox(bbox_js)?;
+        let result = Array::new();
+        let mut stack = vec![&self.core.root];
+
+        while let Some(node) = stack.pop() {
+            if !bbox.intersects(&node.bbox) {
+                continue;
+            }
+            if node.count > max_items && bbox.contains(&node.bbox) {
+                let obj = node.bbox.to_js();
+                let _ = Reflect::set(&obj, &"count".into(), &(node.count as f64).into());
+                result.push(&obj.into());
+                continue;
+            }
+
+            for child in &node.children {
+                if bbox.intersects(child.shape()) {
+                    if child.is_leaf {
+                        result.push(child.data());
+                    } else {
+                        stack.push(child);
+                    }
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    // Estimated number of matches, looking at most two levels below the root.
+    // Nodes inside the box count in full; nodes still crossing the box edge
+    // at that depth count in proportion to the covered share of their area,
+    // which assumes items are spread evenly within each node.
+    #[wasm_bindgen(js_name = estimateCount)]
+    pub fn estimate_count(&self, bbox_js: &JsValue) -> Result<f64, JsError> {
+        const MAX_DEPTH: usize = 2;
+        let bbox = self.read_bbox(bbox_js)?;
+        let mut estimate = 0.0;
+        let mut stack = vec![(&self.core.root, 0)];
+
+        while let Some((node, depth)) = stack.pop() {
+            if !bbox.intersects(&node.bbox) {
+                continue;
+            }
+            if node.is_leaf || bbox.contains(&node.bbox) {
+                estimate += node.count as f64;
+            } else if depth == MAX_DEPTH {
+                let area = node.bbox.area();
+                let share = if area > 0.0 {
+                    bbox.intersection_area(&node.bbox) / area
+                } else {
+                    1.0
+                };
+                estimate += share * node.count as f64;
+            } else {
+                stack.extend(node.children.iter().map(|child| (child, depth + 1)));
+            }
+        }
+        Ok(estimate)
+    }
+
+    // `search` that stops after visiting `max_nodes` nodes intersecting the
+    // box (disjoint nodes are skipped without charging the budget). Returns
+    // {results, complete}; `complete` is true when no intersecting node was
+    // left unvisited, and when false the results are whatever the
+    // depth-first traversal reached first, not any particular region.
+    #[wasm_bindgen(js_name = searchBudgeted)]
+    pub fn search_budgeted(&self, bbox_js: &JsValue, max_nodes: usize) -> Result<JsValue, JsError> {
+        let bbox = self.read_bbox(bbox_js)?;
+        let results = Array::new();
+        let mut stack = vec![&self.core.root];
+        let mut visited = 0;
+
+        while let Some(node) = stack.pop() {
+            if !bbox.intersects(&node.bbox) {
+                continue;
+            }
+            if visited == max_nodes {
+                stack.push(node);
+                break;
+            }
+            visited += 1;
+
+            for child in &node.children {
+                if bbox.intersects(child.shape()) {
+                    if child.is_leaf {
+                        results.push(child.data());
+                    } else {
+                        stack.push(child);
+                    }
+                }
+            }
+        }
+
+        let obj = Object::new();
+        let _ = Reflect::set(&obj, &"results".into(), &results.into());
+        let _ = Reflect::set(&obj, &"complete".into(), &stack.is_empty().into());
+        Ok(obj.into())
+    }
+
+    // Same matches as `search`, collected by a breadth-first traversal.
+    // The tree is balanced and every item lives in a bottom-level node, so
+    // the results come grouped by leaf node, in left-to-right order across
+    // the bottom level, rather than in `search`'s depth-first order.
+    #[wasm_bindgen(js_name = searchBfs)]
+    pub fn search_bfs(&self, bbox_js: &JsValue) -> Result<Array, JsError> {
+        let bbox = self.read_bbox(bbox_js)?;
+        let result = Array::new();
+        let mut queue = std::collections::VecDeque::from([&self.core.root]);
+
+        while let Some(node) = queue.pop_front() {
+            for child in &node.children {
+                if bbox.intersects(child.shape()) {
+                    if child.is_leaf {
+                        result.push(child.data());
+                    } else {
+                        queue.push_back(child);
+                    }
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    // Items crossed by the vertical line at `x` (bbox spans it, inclusive).
+    #[wasm_bindgen(js_name = searchVLine)]
+    pub fn search_vline(&self, x: f64) -> Array {
+        self.search_by(|bbox| bbox.min_x <= x && x <= bbox.max_x)
+    }
+
+    // Items crossed by the horizontal line at `y` (bbox spans it, inclusive).
+    #[wasm_bindgen(js_name = searchHLine)]
+    pub fn search_hline(&self, y: f64) -> Array {
+        self.search_by(|bbox| bbox.min_y <= y && y <= bbox.max_y)
+    }
+
+    // Same matches as `search` as index-aligned columns: one Float64Array per
+    // bbox coordinate plus an Array of the item data.
+    #[wasm_bindgen(js_name = searchSoa)]
+    pub fn search_soa(&self, bbox_js: &JsValue) -> Result<JsValue, JsError> {
+        let leaves = self.matching_leaves(&self.read_bbox(bbox_js)?);
+        let mut min_x = Vec::with_capacity(leaves.len());
+        let mut min_y = Vec::with_capacity(leaves.len());
+        let mut max_x = Vec::with_capacity(leaves.len());
+        let mut max_y = Vec::with_capacity(leaves.len());
+        let data = Array::new_with_length(leaves.len() as u32);
+
+        for (i, leaf) in leaves.iter().enumerate() {
+            let b = leaf.shape();
+            min_x.push(b.min_x);
+            min_y.push(b.min_y);
+            max_x.push(b.max_x);
+            max_y.push(b.max_y);
+            data.set(i as u32, leaf.data().clone());
+        }
+
+        let obj = Object::new();
+        let _ = Reflect::set(&obj, &"minX".into(), &Float64Array::from(min_x.as_slice()).into());
+        let _ = Reflect::set(&obj, &"minY".into(), &Float64Array::from(min_y.as_slice()).into());
+        let _ = Reflect::set(&obj, &"maxX".into(), &Float64Array::from(max_x.as_slice()).into());
+        let _ = Reflect::set(&obj, &"maxY".into(), &Float64Array::from(max_y.as_slice()).into());
+        let _ = Reflect::set(&obj, &"data".into(), &data.into());
+        Ok(obj.into())
+    }
+
+    // Same matches as `search` as {buffer, data}: `buffer` is an ArrayBuffer
+    // (transferable between workers) of SEARCH_RECORD_BYTES-byte
+    // little-endian records, minX, minY, maxX, maxY as f64 at offsets 0-31,
+    // then a u32 index into `data` at 32 and 4 zero bytes of padding, so a
+    // Float64Array over the buffer stays aligned.
+    #[wasm_bindgen(js_name = searchBuffer)]
+    pub fn search_buffer(&self, bbox_js: &JsValue) -> Result<JsValue, JsError> {
+        let leaves = self.matching_leaves(&self.read_bbox(bbox_js)?);
+        let mut bytes = Vec::with_capacity(leaves.len() * SEARCH_RECORD_BYTES);
+        let data = Array::new_with_length(leaves.len() as u32);
+
+        for (i, leaf) in leaves.iter().enumerate() {
+            let extent = leaf.shape();
+            for coord in [extent.min_x, extent.min_y, extent.max_x, extent.max_y] {
+                bytes.extend_from_slice(&coord.to_le_bytes());
+            }
+            bytes.extend_from_slice(&(i as u32).to_le_bytes());
+            bytes.extend_from_slice(&[0; 4]);
+            data.set(i as u32, leaf.data().clone());
+        }
+
+        let obj = Object::new();
+        let _ = Reflect::set(&obj, &"buffer".into(), &Uint8Array::from(bytes.as_slice()).buffer().into());
+        let _ = Reflect::set(&obj, &"data".into(), &data.into());
+        Ok(obj.into())
+    }
+
+    // Same matches as `search` as {minX, minY, maxX, maxY, data} objects
+    // carrying each item's stored bbox alongside its data.
+    #[wasm_bindgen(js_name = searchEntries)]
+    pub fn search_entries(&self, bbox_js: &JsValue) -> Result<Array, JsError> {
+        let result = Array::new();
+        for leaf in self.matching_leaves(&self.read_bbox(bbox_js)?) {
+            let obj = leaf.shape().to_js();
+            let _ = Reflect::set(&obj, &"data".into(), leaf.data());
+            result.push(&obj.into());
+        }
+        Ok(result)
+    }
+
+    // Same results as `search`, written into an array pre-sized to `expected`
+    // and truncated to the real count, avoiding repeated growth of large
+    // result arrays.
+    #[wasm_bindgen(js_name = searchCapacity)]
+    pub fn search_capacity(&self, bbox_js: &JsValue, expected: usize) -> Result<Array, JsError> {
+        let bbox = self.read_bbox(bbox_js)?;
+        let result = Array::new_with_length(expected as u32);
+        let mut count = 0;
+
+        for leaf in self.matching_leaves(&bbox) {
+            result.set(count, leaf.data().clone());
+            count += 1;
+        }
+        result.set_length(count);
+        Ok(result)
+    }
+
+    // Width / height must fall in `[min_ratio, max_ratio]`. Zero-height boxes
+    // have an infinite ratio; zero-size boxes have none and never match.
+    #[wasm_bindgen(js_name = searchByAspect)]
+    pub fn search_by_aspect(&self, bbox_js: &JsValue, min_ratio: f64, max_ratio: f64) -> Result<Array, JsError> {
+        let bbox = self.read_bbox(bbox_js)?;
+        let result = Array::new();
+
+        for leaf in self.matching_leaves(&bbox) {
+            let b = leaf.shape();
+            let width = b.max_x - b.min_x;
+            let height = b.max_y - b.min_y;
+            let ratio = if height > 0.0 {
+                width / height
+            } else if width > 0.0 {
+                f64::INFINITY
+            } else {
+                continue;
+            };
+
+            if min_ratio <= ratio && ratio <= max_ratio {
+                result.push(leaf.data());
+            }
+        }
+        Ok(result)
+    }
+
+    // Items intersecting the rectangle of half extents (half_w, half_h)
+    // centered on (cx, cy) and rotated by `angle` radians counterclockwise.
+    // The tree is pruned with the rectangle's axis-aligned bounds, so the
+    // exact separating-axis test only has to check the rectangle's own two
+    // axes against each candidate.
+    #[wasm_bindgen(js_name = searchObb)]
+    pub fn search_obb(&self, cx: f64, cy: f64, half_w: f64, half_h: f64, angle: f64) -> Array {
+        let (sin, cos) = angle.sin_cos();
+        let ext_x = (half_w * cos).abs() + (half_h * sin).abs();
+        let ext_y = (half_w * sin).abs() + (half_h * cos).abs();
+        let bounds = Rect {
+            min_x: cx - ext_x,
+            min_y: cy - ext_y,
+            max_x: cx + ext_x,
+            max_y: cy + ext_y,
+        };
+
+        let result = Array::new();
+        for leaf in self.matching_leaves(&bounds) {
+            let b = leaf.shape();
+            let (lx, ly) = b.center();
+            let (dx, dy) = (lx - cx, ly - cy);
+            let hx = (b.max_x - b.min_x) / 2.0;
+            let hy = (b.max_y - b.min_y) / 2.0;
+
+            let separated = [(cos, sin, half_w), (-sin, cos, half_h)]
+                .iter()
+                .any(|&(ax, ay, radius)| {
+                    (dx * ax + dy * ay).abs() > radius + hx * ax.abs() + hy * ay.abs()
+                });
+            if !separated {
+                result.push(leaf.data());
+            }
+        }
+        result
+    }
+
+    // Items within `buffer` of the polyline given as flat `[x0, y0, x1, y1,
+    // ...]` (a trailing odd value is ignored; a single point is a zero-length
+    // path). Each segment prunes the tree with its bounds grown by `buffer`,
+    // then candidates are kept when `segment_box_dist` is at most `buffer`.
+    // An item near several segments is returned once, at its first match.
+    #[wasm_bindgen(js_name = searchPath)]
+    pub fn search_path(&self, coords: &[f64], buffer: f64) -> Array {
+        let points: Vec<(f64, f64)> = coords.chunks_exact(2).map(|p| (p[0], p[1])).collect();
+        let segments: Vec<((f64, f64), (f64, f64))> = match points.len() {
+            0 => vec![],
+            1 => vec![(points[0], points[0])],
+            _ => points.windows(2).map(|w| (w[0], w[1])).collect(),
+        };
+
+        let result = Array::new();
+        let mut seen = HashSet::new();
+        for (a, b) in segments {
+            let bounds = Rect {
+                min_x: a.0.min(b.0) - buffer,
+                min_y: a.1.min(b.1) - buffer,
+                max_x: a.0.max(b.0) + buffer,
+                max_y: a.1.max(b.1) + buffer,
+            };
+            for leaf in self.matching_leaves(&bounds) {
+                if segment_box_dist(a, b, leaf.shape()) <= buffer && seen.insert(leaf as *const Entry) {
+                    result.push(leaf.data());
+                }
+            }
+        }
+        result
+    }
+
+    // Sorted by `min(cx - minX, maxX - cx, cy - minY, maxY - cy)` for each
+    // match's center (cx, cy) against the query box: the distance to the
+    // nearest edge, negative when the center lies outside the box.
+    #[wasm_bindgen(js_name = searchEdgeSorted)]
+    pub fn search_edge_sorted(&self, bbox_js: &JsValue) -> Result<Array, JsError> {
+        let bbox = self.read_bbox(bbox_js)?;
+        let mut matches: Vec<(f64, &Entry)> = self
+            .matching_leaves(&bbox)
+            .into_iter()
+            .map(|leaf| {
+                let (cx, cy) = leaf.shape().center();
+                let edge = f64::min(
+                    f64::min(cx - bbox.min_x, bbox.max_x - cx),
+                    f64::min(cy - bbox.min_y, bbox.max_y - cy),
+                );
+                (edge, leaf)
+            })
+            .collect();
+        matches.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let result = Array::new();
+        for (_, leaf) in matches {
+            result.push(leaf.data());
+        }
+        Ok(result)
+    }
+
+    // The `k` intersecting items with the smallest minX, in ascending minX
+    // order. With fewer than `k` matches, all of them come back, sorted the
+    // same way; `k = 0` returns nothing.
+    #[wasm_bindgen(js_name = searchSweep)]
+    pub fn search_sweep(&self, bbox_js: &JsValue, k: usize) -> Result<Array, JsError> {
+        let bbox = self.read_bbox(bbox_js)?;
+        let mut matches = self.matching_leaves(&bbox);
+
+        let by_min_x = |a: &&Entry, b: &&Entry| a.shape().min_x.total_cmp(&b.shape().min_x);
+        if k < matches.len() {
+            if k > 0 {
+                matches.select_nth_unstable_by(k - 1, by_min_x);
+            }
+            matches.truncate(k);
+        }
+        matches.sort_by(by_min_x);
+
+        let result = Array::new();
+        for leaf in matches {
+            result.push(leaf.data());
+        }
+        Ok(result)
+    }
+
+    // Intersecting items with `time >= since`: the bound is inclusive, so
+    // an item stamped exactly `since` matches. Untimed items never do.
+    // Nodes carry their subtree's newest time, pruning all-older subtrees.
+    #[wasm_bindgen(js_name = searchSince)]
+    pub fn search_since(&self, bbox_js: &JsValue, since: f64) -> Result<Array, JsError> {
+        let bbox = self.read_bbox(bbox_js)?;
+        let result = Array::new();
+        let mut stack = vec![&self.core.root];
+
+        while let Some(node) = stack.pop() {
+            if !bbox.intersects(&node.bbox) || node.time < since {
+                continue;
+            }
+
+            for child in &node.children {
+                if bbox.intersects(child.shape()) && child.time >= since {
+                    if child.is_leaf {
+                        result.push(child.data());
+                    } else {
+                        stack.push(child);
+                    }
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    // Items whose tag mask shares a bit with `mask`. Nodes carry the OR of
+    // their subtree's masks, so subtrees without any wanted tag are skipped.
+    #[wasm_bindgen(js_name = searchTags)]
+    pub fn search_tags(&self, bbox_js: &JsValue, mask: u32) -> Result<Array, JsError> {
+        let bbox = self.read_bbox(bbox_js)?;
+        let result = Array::new();
+        let mut stack = vec![&self.core.root];
+
+        while let Some(node) = stack.pop() {
+            if !bbox.intersects(&node.bbox) || node.tags & mask == 0 {
+                continue;
+            }
+
+            for child in &node.children {
+                if bbox.intersects(child.shape()) && child.tags & mask != 0 {
+                    if child.is_leaf {
+                        result.push(child.data());
+                    } else {
+                        stack.push(child);
+                    }
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    // Items intersecting the box for which `predicate(data, minX, minY,
+    // maxX, maxY)` is truthy, called on each hit as the traversal reaches
+    // it. Without a predicate this is `search`. A throwing predicate stops
+    // the search and the error is rethrown.
+    #[wasm_bindgen(js_name = searchPredicate)]
+    pub fn search_predicate(&self, bbox_js: &JsValue, predicate: Option<Function>) -> Result<Array, JsValue> {
+        let bbox = self.read_bbox(bbox_js)?;
+        let Some(predicate) = predicate else {
+            return Ok(self.search_in(&self.core.root, &bbox));
+        };
+        let result = Array::new();
+        let mut stack = vec![&self.core.root];
+
+        while let Some(node) = stack.pop() {
+            if !bbox.intersects(&node.bbox) {
+                continue;
+            }
+
+            for child in &node.children {
+                if !bbox.intersects(child.shape()) {
+                    continue;
+                }
+                if !child.is_leaf {
+                    stack.push(child);
+                    continue;
+                }
+                let data = child.data();
+                let args = Array::of5(
+                    data,
+                    &child.shape().min_x.into(),
+                    &child.shape().min_y.into(),
+                    &child.shape().max_x.into(),
+                    &child.shape().max_y.into(),
+                );
+                if predicate.apply(&JsValue::NULL, &args)?.is_truthy() {
+                    result.push(data);
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    // Calls `callback(data)` for each item intersecting the box, in
+    // traversal order, without building a result array. Returning `false`
+    // (exactly) from the callback stops the traversal; a throw stops it and
+    // is rethrown.
+    #[wasm_bindgen(js_name = searchEach)]
+    pub fn search_each(&self, bbox_js: &JsValue, callback: &Function) -> Result<(), JsValue> {
+        let bbox = self.read_bbox(bbox_js)?;
+        let mut stack = vec![&self.core.root];
+
+        while let Some(node) = stack.pop() {
+            if !bbox.intersects(&node.bbox) {
+                continue;
+            }
+
+            for child in &node.children {
+                if !bbox.intersects(child.shape()) {
+                    continue;
+                }
+                if !child.is_leaf {
+                    stack.push(child);
+                    continue;
+                }
+                let data = child.data();
+                if callback.call1(&JsValue::NULL, data)?.as_bool() == Some(false) {
+                    return Ok(());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Groups reflect the current node layout and change as the tree is mutated.
+    #[wasm_bindgen(js_name = searchByLeaf)]
+    pub fn search_by_leaf(&self, bbox_js: &JsValue) -> Result<Array, JsError> {
+        let bbox = self.read_bbox(bbox_js)?;
+        let result = Array::new();
+        let mut stack = vec![&self.core.root];
+
+        while let Some(node) = stack.pop() {
+            if !bbox.intersects(&node.bbox) {
+                continue;
+            }
+
+            let group = Array::new();
+            for child in &node.children {
+                if bbox.intersects(child.shape()) {
+                    if child.is_leaf {
+                        group.push(child.data());
+                    } else {
+                        stack.push(child);
+                    }
+                }
+            }
+            if group.length() > 0 {
+                result.push(&group.into());
+            }
+        }
+        Ok(result)
+    }
+
+    // `inside` holds the matches; `nearby` holds the other items of every
+    // leaf node that had a match. What counts as nearby therefore follows the
+    // tree's own leaf grouping, and changes as the tree is reorganized.
+    #[wasm_bindgen(js_name = searchPartition)]
+    pub fn search_partition(&self, bbox_js: &JsValue) -> Result<JsValue, JsError> {
+        let bbox = self.read_bbox(bbox_js)?;
+        let inside = Array::new();
+        let nearby = Array::new();
+        let mut stack = vec![&self.core.root];
+
+        while let Some(node) = stack.pop() {
+            if !bbox.intersects(&node.bbox) {
+                continue;
+            }
+            if node.height > 1 {
+                stack.extend(node.children.iter());
+                continue;
+            }
+
+            let (hits, misses): (Vec<&Entry>, Vec<&Entry>) = node
+                .children
+                .iter()
+                .partition(|child| bbox.intersects(child.shape()));
+            if !hits.is_empty() {
+                for child in hits {
+                    inside.push(child.data());
+                }
+                for child in misses {
+                    nearby.push(child.data());
+                }
+            }
+        }
+
+        let obj = Object::new();
+        let _ = Reflect::set(&obj, &"inside".into(), &inside.into());
+        let _ = Reflect::set(&obj, &"nearby".into(), &nearby.into());
+        Ok(obj.into())
+    }
+
+    // Each item intersecting the bbox, paired with up to `k` of the other
+    // matches nearest to its bbox center (nearest first). Loads the `r`
+    // matches into a temporary tree and runs one knn query per match, so
+    // it costs O(r · knn) on top of the search: meant for small result sets.
+    #[wasm_bindgen(js_name = searchWithKnn)]
+    pub fn search_with_knn(&self, bbox_js: &JsValue, k: usize) -> Result<Array, JsError> {
+        let bbox = self.read_bbox(bbox_js)?;
+        let matches: Vec<Entry> = self
+            .matching_leaves(&bbox)
+            .into_iter()
+            .cloned()
+            .collect();
+
+        let mut local = RBush::new(Some(self.core.max_entries), None, None);
+        local.metric = self.metric;
+        local.core.load_entries(matches);
+
+        let result = Array::new();
+        for leaf in local.matching_leaves(&bbox) {
+            let (x, y) = leaf.shape().center();
+            let neighbors = Array::new();
+            for (_, neighbor) in local.nearest_leaves(x, y, k, |other| !std::ptr::eq(other, leaf)) {
+                neighbors.push(neighbor.data());
+            }
+
+            let obj = Object::new();
+            let _ = Reflect::set(&obj, &"data".into(), leaf.data());
+            let _ = Reflect::set(&obj, &"neighbors".into(), &neighbors.into());
+            result.push(&obj.into());
+        }
+        Ok(result)
+    }
+
+    // Union of the true extents of the intersecting items as a plain box,
+    // or null when nothing matches; no result array is built. Padding can
+    // grow a node's bbox past its items, so a node inside the query box
+    // doesn't bound its items tightly: every intersecting node is descended
+    // into, and the cost is that of a full `search`.
+    #[wasm_bindgen(js_name = searchBounds)]
+    pub fn search_bounds(&self, bbox_js: &JsValue) -> Result<JsValue, JsError> {
+        let bbox = self.read_bbox(bbox_js)?;
+        let mut bounds = Rect::new_empty();
+        let mut found = false;
+        let mut stack = vec![&self.core.root];
+
+        while let Some(node) = stack.pop() {
+            if !bbox.intersects(&node.bbox) {
+                continue;
+            }
+
+            for child in &node.children {
+                if bbox.intersects(child.shape()) {
+                    if child.is_leaf {
+                        bounds.extend(child.shape());
+                        found = true;
+                    } else {
+                        stack.push(child);
+                    }
+                }
+            }
+        }
+
+        if found {
+            Ok(bounds.to_js().into())
+        } else {
+            Ok(JsValue::NULL)
+        }
+    }
+
+    // Average of the matching bbox centers. With `weighted`, each center is
+    // weighted by its bbox area (falling back to the plain average when all
+    // matches are zero-area).
+    pub fn centroid(&self, bbox_js: &JsValue, weighted: Option<bool>) -> Result<JsValue, JsError> {
+        let bbox = self.read_bbox(bbox_js)?;
+        let matches = self.matching_leaves(&bbox);
+        if matches.is_empty() {
+            return Ok(JsValue::NULL);
+        }
+
+        let (mut sum_x, mut sum_y, mut sum_w) = (0.0, 0.0, 0.0);
+        if weighted.unwrap_or(false) {
+            for leaf in &matches {
+                let (x, y) = leaf.shape().center();
+                let w = leaf.shape().area();
+                sum_x += x * w;
+                sum_y += y * w;
+                sum_w += w;
+            }
+        }
+        if sum_w <= 0.0 {
+            (sum_x, sum_y) = (0.0, 0.0);
+            for leaf in &matches {
+                let (x, y) = leaf.shape().center();
+                sum_x += x;
+                sum_y += y;
+            }
+            sum_w = matches.len() as f64;
+        }
+
+        let obj = Object::new();
+        let _ = Reflect::set(&obj, &"x".into(), &(sum_x / sum_w).into());
+        let _ = Reflect::set(&obj, &"y".into(), &(sum_y / sum_w).into());
+        Ok(obj.into())
+    }
+
+    // Greedy decimation in traversal order: a match is kept unless its center
+    // lies closer than `min_separation` to an already kept center. Accepted
+    // centers live in a hash grid of `min_separation`-sized cells, so each
+    // check only looks at the 3x3 neighborhood.
+    #[wasm_bindgen(js_name = searchThinned)]
+    pub fn search_thinned(&self, bbox_js: &JsValue, min_separation: f64) -> Result<Array, JsError> {
+        let bbox = self.read_bbox(bbox_js)?;
+        if min_separation.is_nan() || min_separation <= 0.0 {
+            return Ok(self.search_in(&self.core.root, &bbox));
+        }
+
+        let result = Array::new();
+        let min_dist_sq = min_separation * min_separation;
+        let mut grid: HashMap<(i64, i64), Vec<(f64, f64)>> = HashMap::new();
+
+        for leaf in self.matching_leaves(&bbox) {
+            let (x, y) = leaf.shape().center();
+            let cx = (x / min_separation).floor() as i64;
+            let cy = (y / min_separation).floor() as i64;
+
+            let crowded = (-1..=1).any(|dx| {
+                (-1..=1).any(|dy| {
+                    grid.get(&(cx.saturating_add(dx), cy.saturating_add(dy)))
+                        .is_some_and(|points| {
+                            points.iter().any(|&(px, py)| {
+                                (px - x) * (px - x) + (py - y) * (py - y) < min_dist_sq
+                            })
+                        })
+                })
+            });
+            if !crowded {
+                grid.entry((cx, cy)).or_default().push((x, y));
+                result.push(leaf.data());
+            }
+        }
+        Ok(result)
+    }
+
+    // Items whose bbox comes within Euclidean distance `r` of (cx, cy), i.e.
+    // intersects the disc, whatever the current metric. Nodes whose squared
+    // distance from the center exceeds `r * r` are skipped.
+    #[wasm_bindgen(js_name = searchRadius)]
+    pub fn search_radius(&self, cx: f64, cy: f64, r: f64) -> Array {
+        let result = Array::new();
+        if r < 0.0 {
+            return result;
+        }
+        let r_sq = r * r;
+        let dist_sq = |rect: &Rect| {
+            let (dx, dy) = rect.point_gap(cx, cy);
+            dx * dx + dy * dy
+        };
+        let mut stack = vec![&self.core.root];
+
+        while let Some(node) = stack.pop() {
+            if dist_sq(&node.bbox) > r_sq {
+                continue;
+            }
+
+            for child in &node.children {
+                if dist_sq(child.shape()) <= r_sq {
+                    if child.is_leaf {
+                        let data = child.data();
+                        result.push(data);
+                    } else {
+                        stack.push(child);
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    // {data, distance} for every item within `radius` of the point, distance
+    // being the current metric's point-to-bbox distance (0 inside the bbox).
+    // Nodes farther than `radius` are pruned. Results are in traversal order
+    // unless `sorted` asks for nearest first.
+    #[wasm_bindgen(js_name = searchRadiusWithDistance)]
+    pub fn search_radius_with_distance(&self, x: f64, y: f64, radius: f64, sorted: Option<bool>) -> Array {
+        let metric = self.metric;
+        let max_rank = metric.rank_of(radius);
+        let mut matches = Vec::new();
+        let mut stack = vec![&self.core.root];
+
+        while let Some(node) = stack.pop() {
+            if metric.rank(&node.bbox, x, y) > max_rank {
+                continue;
+            }
+
+            for child in &node.children {
+                let rank = metric.rank(child.shape(), x, y);
+                if rank <= max_rank {
+                    if child.is_leaf {
+                        matches.push((rank, child));
+                    } else {
+                        stack.push(child);
+                    }
+                }
+            }
+        }
+        if sorted.unwrap_or(false) {
+            matches.sort_by(|a, b| a.0.total_cmp(&b.0));
+        }
+
+        let result = Array::new();
+        for (rank, leaf) in matches {
+            let obj = Object::new();
+            let _ = Reflect::set(&obj, &"data".into(), leaf.data());
+            let _ = Reflect::set(&obj, &"distance".into(), &metric.distance(rank).into());
+            result.push(&obj.into());
+        }
+        result
+    }
+
+    // Ranks items by the squared box-to-box gap: the per-axis separation
+    // (0 when overlapping on that axis) combined as dx^2 + dy^2, so every
+    // item intersecting the box is at distance 0.
+    #[wasm_bindgen(js_name = knnBox)]
+    pub fn knn_box(&self, bbox_js: &JsValue, k: usize) -> Result<Array, JsError> {
+        let bbox = self.read_bbox(bbox_js)?;
+        let result = Array::new();
+        for (_, leaf) in self.nearest_leaves_by(|other| bbox.box_dist_sq(other), k, f64::INFINITY, |_| true) {
+            result.push(leaf.data());
+        }
+        Ok(result)
+    }
+
+    // Up to `k` items nearest to the point, nearest first, by the distance
+    // from the point to each bbox under the current metric (0 inside).
+    pub fn knn(&self, x: f64, y: f64, k: usize) -> Array {
+        let result = Array::new();
+        for (_, leaf) in self.nearest_leaves(x, y, k, |_| true) {
+            result.push(leaf.data());
+        }
+        result
+    }
+
+    // `knn` ranked by `dist(minX, minY, maxX, maxY)`, the caller's distance
+    // from its query point to a box; the callback closes over the point, so
+    // none is passed here. The early exit is only exact if the callback
+    // never gives a node more than anything inside it. A throw or a
+    // non-number result stops the search with an error.
+    #[wasm_bindgen(js_name = knnWithDistance)]
+    pub fn knn_with_distance(&self, k: usize, dist: &Function) -> Result<Array, JsValue> {
+        let error = RefCell::new(None);
+        let leaves = self.nearest_leaves_by(
+            |bbox| {
+                if error.borrow().is_some() {
+                    return f64::INFINITY;
+                }
+                let args = Array::of4(&bbox.min_x.into(), &bbox.min_y.into(), &bbox.max_x.into(), &bbox.max_y.into());
+                let value = match dist.apply(&JsValue::NULL, &args) {
+                    Ok(value) => value,
+                    Err(err) => {
+                        *error.borrow_mut() = Some(err);
+                        return f64::INFINITY;
+                    }
+                };
+                value.as_f64().unwrap_or_else(|| {
+                    *error.borrow_mut() = Some(JsError::new("distance callback must return a number").into());
+                    f64::INFINITY
+                })
+            },
+            k,
+            f64::INFINITY,
+            |_| error.borrow().is_none(),
+        );
+        if let Some(err) = error.into_inner() {
+            return Err(err);
+        }
+        let result = Array::new();
+        for (_, leaf) in leaves {
+            result.push(leaf.data());
+        }
+        Ok(result)
+    }
+
+    // `knn` limited to items at most `max_distance` away (inclusive); may
+    // return fewer than `k`. Subtrees beyond the cutoff are never queued.
+    #[wasm_bindgen(js_name = knnWithin)]
+    pub fn knn_within(&self, x: f64, y: f64, k: usize, max_distance: f64) -> Array {
+        let metric = self.metric;
+        let max_rank = metric.rank_of(max_distance);
+        let result = Array::new();
+        for (_, leaf) in self.nearest_leaves_by(|bbox| metric.rank(bbox, x, y), k, max_rank, |_| true) {
+            result.push(leaf.data());
+        }
+        result
+    }
+
+    // Nearest item (by the current metric) for which `predicate(data)` is
+    // truthy, or null. Candidates are tested in distance order, so the
+    // search stops at the first accepted item. A throwing predicate stops
+    // the search and the error is rethrown.
+    #[wasm_bindgen(js_name = nearestWhere)]
+    pub fn nearest_where(&self, x: f64, y: f64, predicate: &Function) -> Result<JsValue, JsValue> {
+        let mut error = None;
+        let found = self.nearest_leaves(x, y, 1, |leaf| match predicate.call1(&JsValue::NULL, leaf.data()) {
+            Ok(value) => value.is_truthy(),
+            Err(err) => {
+                error = Some(err);
+                true
+            }
+        });
+        if let Some(err) = error {
+            return Err(err);
+        }
+        Ok(match found.first() {
+            Some((_, leaf)) => leaf.data().clone(),
+            None => JsValue::NULL,
+        })
+    }
+
+    // Item nearest (by the current metric) to the centroid of the flat
+    // `[x0, y0, x1, y1, ...]` points; a trailing odd value is ignored.
+    // Null when the tree or the point list is empty.
+    #[wasm_bindgen(js_name = nearestToPoints)]
+    pub fn nearest_to_points(&self, coords: &[f64]) -> JsValue {
+        let n = coords.len() / 2;
+        if n == 0 {
+            return JsValue::NULL;
+        }
+        let (mut sum_x, mut sum_y) = (0.0, 0.0);
+        for point in coords.chunks_exact(2) {
+            sum_x += point[0];
+            sum_y += point[1];
+        }
+
+        let (x, y) = (sum_x / n as f64, sum_y / n as f64);
+        match self.nearest_leaves(x, y, 1, |_| true).first() {
+            Some((_, leaf)) => leaf.data().clone(),
+            None => JsValue::NULL,
+        }
+    }
+
+    // {data, nnDistance} for every item, nnDistance being the gap to the
+    // closest other item's bbox (as in `knn_box`: Euclidean, 0 when they
+    // touch or overlap), or null for a lone item. One best-first search per
+    // item, O(n log n) overall on a well-built tree.
+    #[wasm_bindgen(js_name = allNnDistances)]
+    pub fn all_nn_distances(&self) -> Array {
+        let result = Array::new();
+        let mut stack = vec![&self.core.root];
+
+        while let Some(node) = stack.pop() {
+            for leaf in &node.children {
+                if !leaf.is_leaf {
+                    stack.push(leaf);
+                    continue;
+                }
+                let nearest = self.nearest_leaves_by(
+                    |other| leaf.shape().box_dist_sq(other),
+                    1,
+                    f64::INFINITY,
+                    |other| !std::ptr::eq(other, leaf),
+                );
+
+                let obj = Object::new();
+                let _ = Reflect::set(&obj, &"data".into(), leaf.data());
+                let distance = match nearest.first() {
+                    Some((dist_sq, _)) => dist_sq.sqrt().into(),
+                    None => JsValue::NULL,
+                };
+                let _ = Reflect::set(&obj, &"nnDistance".into(), &distance);
+                result.push(&obj.into());
+            }
+        }
+        result
+    }
+
+    // Convex hull of the corners of every matching bbox, as a flat
+    // `[x0, y0, x1, y1, ...]` counter-clockwise ring.
+    #[wasm_bindgen(js_name = searchHull)]
+    pub fn search_hull(&self, bbox_js: &JsValue) -> Result<Array, JsError> {
+        let bbox = self.read_bbox(bbox_js)?;
+        let mut corners = Vec::new();
+        for leaf in self.matching_leaves(&bbox) {
+            let b = leaf.shape();
+            corners.extend([
+                (b.min_x, b.min_y),
+                (b.max_x, b.min_y),
+                (b.max_x, b.max_y),
+                (b.min_x, b.max_y),
+            ]);
+        }
+
+        let result = Array::new();
+        for (x, y) in convex_hull(corners) {
+            result.push(&x.into());
+            result.push(&y.into());
+        }
+        Ok(result)
+    }
+
+    // Smallest circle around the corners of every matching bbox, as
+    // {cx, cy, radius}; a single point gives radius 0. Null when nothing
+    // matches. Expected O(r) for `r` matches (randomized Welzl).
+    #[wasm_bindgen(js_name = searchEnclosingCircle)]
+    pub fn search_enclosing_circle(&self, bbox_js: &JsValue) -> Result<JsValue, JsError> {
+        let bbox = self.read_bbox(bbox_js)?;
+        let mut corners = Vec::new();
+        for leaf in self.matching_leaves(&bbox) {
+            let b = leaf.shape();
+            corners.extend([
+                (b.min_x, b.min_y),
+                (b.max_x, b.min_y),
+                (b.max_x, b.max_y),
+                (b.min_x, b.max_y),
+            ]);
+        }
+
+        match enclosing_circle(corners) {
+            Some((cx, cy, radius)) => {
+                let obj = Object::new();
+                let _ = Reflect::set(&obj, &"cx".into(), &cx.into());
+                let _ = Reflect::set(&obj, &"cy".into(), &cy.into());
+                let _ = Reflect::set(&obj, &"radius".into(), &radius.into());
+                Ok(obj.into())
+            }
+            None => Ok(JsValue::NULL),
+        }
+    }
+
+    pub fn collides(&self, bbox_js: &JsValue) -> Result<bool, JsError> {
+        Ok(self.core.collides(&self.read_bbox(bbox_js)?))
+    }
+
+    // `collides` with a cap of `max_checks` visited nodes. Returns `true` or
+    // `false` when the traversal settles the answer within the cap, and the
+    // string "unknown" when it had to give up; callers needing certainty
+    // should then fall back to `collides`.
+    #[wasm_bindgen(js_name = collidesSampled)]
+    pub fn collides_sampled(&self, bbox_js: &JsValue, max_checks: usize) -> Result<JsValue, JsError> {
+        let bbox = self.read_bbox(bbox_js)?;
+        let mut stack = vec![&self.core.root];
+        let mut checks = 0;
+
+        while let Some(node) = stack.pop() {
+            if checks == max_checks {
+                return Ok("unknown".into());
+            }
+            checks += 1;
+
+            if !bbox.intersects(&node.bbox) {
+                continue;
+            }
+
+            for child in &node.children {
+                if bbox.intersects(child.shape()) {
+                    if child.is_leaf || bbox.contains(child.shape()) {
+                        return Ok(true.into());
+                    }
+                    stack.push(child);
+                }
+            }
+        }
+        Ok(false.into())
+    }
+
+    // The intersecting item with the largest intersection area with the
+    // box, or null when nothing intersects. On a tie the first item the
+    // traversal reaches wins (a later one must overlap strictly more), so
+    // which tied item comes back depends on the tree layout, not on
+    // insertion order. An item only touching the box overlaps by 0.
+    #[wasm_bindgen(js_name = maxOverlap)]
+    pub fn max_overlap(&self, bbox_js: &JsValue) -> Result<JsValue, JsError> {
+        let bbox = self.read_bbox(bbox_js)?;
+        let mut best: Option<(f64, &JsValue)> = None;
+        let mut stack = vec![&self.core.root];
+
+        while let Some(node) = stack.pop() {
+            if !bbox.intersects(&node.bbox) {
+                continue;
+            }
+
+            for child in &node.children {
+                if bbox.intersects(child.shape()) {
+                    if child.is_leaf {
+                        let overlap = bbox.intersection_area(child.shape());
+                        if best.is_none_or(|(max, _)| overlap > max) {
+                            best = Some((overlap, child.data()));
+                        }
+                    } else {
+                        stack.push(child);
+                    }
+                }
+            }
+        }
+        Ok(best.map_or(JsValue::NULL, |(_, data)| data.clone()))
+    }
+
+    // The `k` intersecting items with the largest intersection area with the
+    // box, largest first; all of them, sorted, when fewer than `k` intersect.
+    // Items that only touch the box have an overlap of 0 and rank last.
+    #[wasm_bindgen(js_name = topOverlaps)]
+    pub fn top_overlaps(&self, bbox_js: &JsValue, k: usize) -> Result<Array, JsError> {
+        let bbox = self.read_bbox(bbox_js)?;
+        let result = Array::new();
+        if k == 0 {
+            return Ok(result);
+        }
+
+        // min-heap on overlap, so the weakest of the current top k pops first
+        let mut top = BinaryHeap::with_capacity(k + 1);
+        for leaf in self.matching_leaves(&bbox) {
+            top.push(Candidate {
+                dist: bbox.intersection_area(leaf.shape()),
+                entry: leaf,
+            });
+            if top.len() > k {
+                top.pop();
+            }
+        }
+
+        for candidate in top.into_sorted_vec() {
+            result.push(candidate.entry.data());
+        }
+        Ok(result)
+    }
+
+    // Number of items whose bbox contains the point, edges included: 1
+    // where coverage is clean, 0 in a gap and more than 1 where items
+    // overlap. Items that merely touch all contain a point on their shared
+    // edge, so such a point counts as overlapped.
+    #[wasm_bindgen(js_name = coverageCountAt)]
+    pub fn coverage_count_at(&self, x: f64, y: f64) -> usize {
+        let mut count = 0;
+        let mut stack = vec![&self.core.root];
+
+        while let Some(node) = stack.pop() {
+            if !node.bbox.contains_point(x, y) {
+                continue;
+            }
+
+            for child in &node.children {
+                if child.shape().contains_point(x, y) {
+                    if child.is_leaf {
+                        count += 1;
+                    } else {
+                        stack.push(child);
+                    }
+                }
+            }
+        }
+        count
+    }
+
+    // Approximate gap map: samples the center of each cell of a grid x grid
+    // lattice over the box and reports uncovered cells. Horizontal runs of
+    // uncovered cells are merged, then identical runs on consecutive rows, so
+    // results are only as fine as the grid resolution.
+    #[wasm_bindgen(js_name = findGaps)]
+    pub fn find_gaps(&self, bbox_js: &JsValue, grid: usize) -> Result<Array, JsError> {
+        let bbox = self.read_bbox(bbox_js)?;
+        let result = Array::new();
+        if grid == 0 {
+            return Ok(result);
+        }
+
+        let cell_w = (bbox.max_x - bbox.min_x) / grid as f64;
+        let cell_h = (bbox.max_y - bbox.min_y) / grid as f64;
+        let cell_rect = |c0: usize, c1: usize, r0: usize, r1: usize| Rect {
+            min_x: bbox.min_x + c0 as f64 * cell_w,
+            min_y: bbox.min_y + r0 as f64 * cell_h,
+            max_x: bbox.min_x + c1 as f64 * cell_w,
+            max_y: bbox.min_y + r1 as f64 * cell_h,
+        };
+
+        // (first column, end column, first row) of gaps still growing downwards
+        let mut open: Vec<(usize, usize, usize)> = Vec::new();
+        for row in 0..=grid {
+            let mut runs = Vec::new();
+            if row < grid {
+                let y = bbox.min_y + (row as f64 + 0.5) * cell_h;
+                let mut start = None;
+                for col in 0..=grid {
+                    let uncovered = col < grid
+                        && !self.point_covered(bbox.min_x + (col as f64 + 0.5) * cell_w, y);
+                    match (uncovered, start) {
+                        (true, None) => start = Some(col),
+                        (false, Some(c0)) => {
+                            runs.push((c0, col));
+                            start = None;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+
+            let mut still_open = Vec::new();
+            for (c0, c1, r0) in open {
+                if runs.contains(&(c0, c1)) {
+                    still_open.push((c0, c1, r0));
+                } else {
+                    result.push(&cell_rect(c0, c1, r0, row).to_js().into());
+                }
+            }
+            for (c0, c1) in runs {
+                if !still_open.iter().any(|&(o0, o1, _)| (o0, o1) == (c0, c1)) {
+                    still_open.push((c0, c1, row));
+                }
+            }
+            open = still_open;
+        }
+        Ok(result)
+    }
+
+    #[wasm_bindgen(js_name = insert)]
+    pub fn insert(&mut self, item: JsValue) -> Result<(), JsError> {
+        if !item.is_null() && !item.is_undefined() {
+            let entry = Entry::leaf_with_bbox(self.read_bbox(&item)?, item);
+            self.insert_entry(entry)?;
+        }
+        Ok(())
+    }
+
+    // `insert` for each item in order, in one call. Unlike `load`, every
+    // item goes through the regular insertion path. An error stops the
+    // batch, leaving the earlier items inserted, as a JS loop would.
+    #[wasm_bindgen(js_name = insertMany)]
+    pub fn insert_many(&mut self, items: &Array) -> Result<(), JsError> {
+        for item in items.iter() {
+            self.insert(item)?;
+        }
+        Ok(())
+    }
+
+    // Inserts `data` under the given box instead of reading bbox fields off
+    // it, so any value (a string id, an object without minX etc.) can be
+    // stored. `remove(data)` finds such items by a full-tree scan when the
+    // data has no `minX` field.
+    #[wasm_bindgen(js_name = insertWithBox)]
+    pub fn insert_with_box(&mut self, min_x: f64, min_y: f64, max_x: f64, max_y: f64, data: JsValue) -> Result<(), JsError> {
+        if !data.is_null() && !data.is_undefined() {
+            let bbox = Rect::new(min_x, min_y, max_x, max_y);
+            self.insert_entry(Entry::leaf_with_bbox(bbox, data))?;
+        }
+        Ok(())
+    }
+
+    // Whether inserting an item with this bbox right now would split a node:
+    // follows the insert path's subtree choices down to the leaf node that
+    // would take the item and checks whether it is already full. Reflects
+    // the current tree only; any later change can alter the answer.
+    #[wasm_bindgen(js_name = wouldSplit)]
+    pub fn would_split(&self, bbox_js: &JsValue) -> Result<bool, JsError> {
+        let bbox = self.read_bbox(bbox_js)?;
+        let mut node = &self.core.root;
+        while node.height > 1 && !node.children.is_empty() {
+            node = &node.children[RBushCore::choose_subtree(node, &bbox)];
+        }
+        Ok(node.children.len() >= self.core.max_entries)
+    }
+
+    #[wasm_bindgen(js_name = insertAtTime)]
+    pub fn insert_at_time(&mut self, item: JsValue, time: f64) -> Result<(), JsError> {
+        if !item.is_null() && !item.is_undefined() {
+            let mut entry = Entry::leaf_with_bbox(self.read_bbox(&item)?, item);
+            entry.time = time;
+            self.insert_entry(entry)?;
+        }
+        Ok(())
+    }
+
+    #[wasm_bindgen(js_name = insertTaggedBits)]
+    pub fn insert_tagged_bits(&mut self, item: JsValue, tags: u32) -> Result<(), JsError> {
+        if !item.is_null() && !item.is_undefined() {
+            let mut entry = Entry::leaf_with_bbox(self.read_bbox(&item)?, item);
+            entry.tags = tags;
+            self.insert_entry(entry)?;
+        }
+        Ok(())
+    }
+
+    // Indexes the item under its bbox grown by `pad` on every side, while
+    // `search`, `collides` and the queries built on the same matching still
+    // test its true extent. Queries visit a few more candidates, but the item
+    // can move within the padding without restructuring, see `update_padded`.
+    #[wasm_bindgen(js_name = insertPadded)]
+    pub fn insert_padded(&mut self, item: JsValue, pad: f64) -> Result<(), JsError> {
+        if !item.is_null() && !item.is_undefined() {
+            let extent = self.read_bbox(&item)?;
+            let pad = pad.max(0.0);
+            let mut entry = Entry::leaf_with_bbox(extent.padded(pad), item);
+            entry.extent = extent;
+            entry.pad = pad;
+            self.insert_entry(entry)?;
+        }
+        Ok(())
+    }
+
+    // Re-reads the item's coordinates and, if the new extent still fits the
+    // box it is indexed under, records it in place and returns true. Returns
+    // false otherwise; the item then has to be removed with its old
+    // coordinates and inserted again.
+    #[wasm_bindgen(js_name = updatePadded)]
+    pub fn update_padded(&mut self, item: &JsValue) -> Result<bool, JsError> {
+        let extent = self.read_bbox(item)?;
+        let mut stack = vec![&mut self.core.root];
+
+        while let Some(node) = stack.pop() {
+            for child in &mut node.children {
+                if !child.bbox.contains(&extent) {
+                    continue;
+                }
+                if !child.is_leaf {
+                    stack.push(child);
+                } else if child.data() == item {
+                    child.extent = extent;
+                    return Ok(true);
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    // Moves a stored item to a new box. When the box still fits the leaf
+    // node holding the item, only the item's box and its ancestors' bboxes
+    // change; otherwise the item is unlinked and linked again. Neither path
+    // counts as a removal or an insert in `op_stats` or towards
+    // auto-compaction. The item is found as `remove` finds it, so pass it
+    // before changing its bbox fields, and change them to the new box
+    // afterwards: later lookups (`remove`, `update`) read the item's own
+    // fields. Returns false when the item is not in the tree.
+    pub fn update(&mut self, item: JsValue, new_min_x: f64, new_min_y: f64, new_max_x: f64, new_max_y: f64) -> Result<bool, JsError> {
+        if item.is_null() || item.is_undefined() {
+            return Ok(false);
+        }
+        let old = self.lookup_bbox(&item)?;
+        let new = Rect::new(new_min_x, new_min_y, new_max_x, new_max_y);
+        if self.frozen_nodes > 0 {
+            if self.frozen_holds(&old, |data| data == &item) {
+                return Err(JsError::new("cannot move an item in a frozen region"));
+            }
+            self.check_insert(&new, 0)?;
+        }
+
+        let mut moved_pin = 0;
+        match RBush::move_in_node(&mut self.core.root, &item, &old, &new, &mut moved_pin) {
+            None => return Ok(false),
+            Some(true) => {}
+            Some(false) => {
+                let mut reinsert = Vec::new();
+                let Some(mut entry) = RBushCore::remove_from_node(
+                    &mut self.core.root,
+                    &mut |data| data == &item,
+                    &old,
+                    self.core.min_entries,
+                    &mut reinsert,
+                ) else {
+                    return Ok(false);
+                };
+                self.core.condense(reinsert, 0);
+                entry.bbox = new.padded(entry.pad);
+                entry.extent = new;
+                moved_pin = entry.pin;
+                self.core._insert_at_level(entry, 0);
+            }
+        }
+        if moved_pin != 0 {
+            self.pins.insert(moved_pin, new);
+        }
+        Ok(true)
+    }
+
+    // Finds `item` under `node` (descending only into nodes containing
+    // `old`) and, if `new` fits within its leaf node, gives it the new box
+    // and refits the bboxes on the way back up. A padded item keeps its pad,
+    // so it is `new` grown by the pad that has to fit. None when the item is
+    // not found, Some(false) when it is but doesn't fit.
+    fn move_in_node(node: &mut Entry, item: &JsValue, old: &Rect, new: &Rect, pin: &mut u64) -> Option<bool> {
+        let bbox = node.bbox;
+        for child in &mut node.children {
+            if !child.bbox.contains(old) {
+                continue;
+            }
+            if child.is_leaf {
+                if child.data() != item {
+                    continue;
+                }
+                let padded = new.padded(child.pad);
+                if !bbox.contains(&padded) {
+                    return Some(false);
+                }
+                child.bbox = padded;
+                child.extent = *new;
+                *pin = child.pin;
+            } else {
+                match RBush::move_in_node(child, item, old, new, pin) {
+                    None => continue,
+                    Some(false) => return Some(false),
+                    Some(true) => {}
+                }
+            }
+            node.calc_bbox();
+            return Some(true);
+        }
+        None
+    }
+
+    // Returns an id that keeps resolving to this item across `optimize()` and
+    // other reorganizations. Each pin costs one table entry (id + bbox).
+    #[wasm_bindgen(js_name = insertPinned)]
+    pub fn insert_pinned(&mut self, item: JsValue) -> Result<u64, JsError> {
+        if item.is_null() || item.is_undefined() {
+            return Err(JsError::new("cannot pin a null or undefined item"));
+        }
+        let mut entry = Entry::leaf_with_bbox(self.read_bbox(&item)?, item);
+        entry.pin = self.next_pin;
+        let (pin, bbox) = (entry.pin, entry.bbox);
+        self.insert_entry(entry)?;
+        self.next_pin += 1;
+        self.pins.insert(pin, bbox);
+        Ok(pin)
+    }
+
+    // Resolves a pin by descending only into nodes containing the pinned
+    // bbox, O(log n) on a healthy tree. Returns null once the item is gone.
+    #[wasm_bindgen(js_name = searchPin)]
+    pub fn search_pin(&self, pin: u64) -> JsValue {
+        let Some(bbox) = self.pins.get(&pin) else {
+            return JsValue::NULL;
+        };
+        let mut stack = vec![&self.core.root];
+
+        while let Some(node) = stack.pop() {
+            for child in &node.children {
+                if child.bbox.contains(bbox) {
+                    if !child.is_leaf {
+                        stack.push(child);
+                    } else if child.pin == pin {
+                        return child.data().clone();
+                    }
+                }
+            }
+        }
+        JsValue::NULL
+    }
+
+    // Number of pin table entries; removing a pinned item frees its entry.
+    #[wasm_bindgen(js_name = pinCount)]
+    pub fn pin_count(&self) -> usize {
+        self.pins.len()
+    }
+
+    pub fn load(&mut self, data: &Array) -> Result<(), JsError> {
+        let items: Vec<Entry> = (0..data.length())
+            .filter_map(|i| {
+                let val = data.get(i);
+                if val.is_null() || val.is_undefined() {
+                    return None;
+                }
+                Some(self.read_bbox(&val).map(|bbox| Entry::leaf_with_bbox(bbox, val)))
+            })
+            .collect::<Result<_, _>>()?;
+        self.check_load(&items)?;
+        if !items.is_empty() {
+            self.core.stats.inserts += items.len();
+            self.core.load_entries(items);
+        }
+        Ok(())
+    }
+
+    #[wasm_bindgen(js_name = loadHybrid)]
+    pub fn load_hybrid(&mut self, fast_coords: &[f64], items: &Array) -> Result<(), JsError> {
+        let entries = RBush::hybrid_entries(fast_coords, items);
+        self.check_load(&entries)?;
+        if !entries.is_empty() {
+            self.core.stats.inserts += entries.len();
+            self.core.load_entries(entries);
+        }
+        Ok(())
+    }
+
+    // Bulk loads boxes from flat `[minX, minY, maxX, maxY, ...]` coordinates,
+    // storing `ids[i]` (as a JS number) as the data of box `i`, so no JS
+    // object is created per item.
+    #[wasm_bindgen(js_name = loadFlat)]
+    pub fn load_flat(&mut self, coords: &[f64], ids: &[f64]) -> Result<(), JsError> {
+        if coords.len() != ids.len() * 4 {
+            return Err(JsError::new(&format!(
+                "expected 4 coordinates per id ({} ids), got {} coordinates",
+                ids.len(),
+                coords.len()
+            )));
+        }
+        let entries: Vec<Entry> = coords
+            .chunks_exact(4)
+            .zip(ids)
+            .map(|(bbox, &id)| Entry::leaf_with_bbox(Rect::from_flat(bbox), id.into()))
+            .collect();
+        self.check_load(&entries)?;
+        if !entries.is_empty() {
+            self.core.stats.inserts += entries.len();
+            self.core.load_entries(entries);
+        }
+        Ok(())
+    }
+
+    // Starts a streaming load, discarding any chunks pushed since the last
+    // `finish_load`.
+    #[wasm_bindgen(js_name = beginLoad)]
+    pub fn begin_load(&mut self) {
+        self.pending_load.clear();
+    }
+
+    // Buffers a chunk in the `loadHybrid` layout. Buffered items are not
+    // part of the tree, and no query sees them, until `finish_load`.
+    #[wasm_bindgen(js_name = pushLoadChunk)]
+    pub fn push_load_chunk(&mut self, coords: &[f64], items: &Array) {
+        let entries = RBush::hybrid_entries(coords, items);
+        self.pending_load.extend(entries);
+    }
+
+    // Bulk-loads everything buffered since `begin_load` in one build. The
+    // buffer is kept when the load is rejected.
+    #[wasm_bindgen(js_name = finishLoad)]
+    pub fn finish_load(&mut self) -> Result<(), JsError> {
+        self.check_load(&self.pending_load)?;
+        let entries = std::mem::take(&mut self.pending_load);
+        if !entries.is_empty() {
+            self.core.stats.inserts += entries.len();
+            self.core.load_entries(entries);
+        }
+        Ok(())
+    }
+
+    pub fn remove(&mut self, item: JsValue) -> Result<(), JsError> {
+        if item.is_null() || item.is_undefined() {
+            return Ok(());
+        }
+        let bbox = self.lookup_bbox(&item)?;
+        if self.frozen_nodes > 0 && self.frozen_holds(&bbox, |data| data == &item) {
+            return Err(JsError::new("cannot remove an item from a frozen region"));
+        }
+        let mut reinsert = Vec::new();
+        let removed = RBushCore::remove_from_node(
+            &mut self.core.root,
+            &mut |data| data == &item,
+            &bbox,
+            self.core.min_entries,
+            &mut reinsert,
+        );
+        if let Some(entry) = removed {
+            self.finish_removal(reinsert, &[entry]);
+        }
+        Ok(())
+    }
+
+    // Removes each listed item like `remove` in a loop, but condenses the
+    // tree once: items of underflowed nodes wait in one reinsert list until
+    // the end (and are dropped from it if listed themselves). Items not in
+    // the tree are skipped. Returns how many were removed.
+    #[wasm_bindgen(js_name = removeAll)]
+    pub fn remove_all(&mut self, items: &Array) -> Result<usize, JsError> {
+        let targets: Vec<(JsValue, Rect)> = (0..items.length())
+            .map(|i| items.get(i))
+            .filter(|item| !item.is_null() && !item.is_undefined())
+            .map(|item| {
+                let bbox = self.lookup_bbox(&item)?;
+                Ok((item, bbox))
+            })
+            .collect::<Result<_, JsError>>()?;
+        if self.frozen_nodes > 0
+            && targets
+                .iter()
+                .any(|(item, bbox)| self.frozen_holds(bbox, |data| data == item))
+        {
+            return Err(JsError::new("cannot remove an item from a frozen region"));
+        }
+
+        let mut reinsert = Vec::new();
+        let mut taken = Vec::new();
+        for (item, bbox) in &targets {
+            let removed = RBushCore::remove_from_node(
+                &mut self.core.root,
+                &mut |data| data == item,
+                bbox,
+                self.core.min_entries,
+                &mut reinsert,
+            );
+            if let Some(entry) = removed {
+                taken.push(entry);
+            } else if let Some(i) = reinsert.iter().position(|entry| entry.data() == item) {
+                taken.push(reinsert.swap_remove(i));
+            }
+        }
+        if !taken.is_empty() {
+            self.finish_removal(reinsert, &taken);
+        }
+        Ok(taken.len())
+    }
+
+    // `remove` that matches by `eq(candidate, item)` instead of identity,
+    // like rbush's `equalsFn`, so a fresh object with the same fields can
+    // remove the stored one. `item`'s bbox still prunes the search, and
+    // only the first match is removed. Errors thrown by `eq` are rethrown
+    // with the tree unchanged.
+    #[wasm_bindgen(js_name = removeWith)]
+    pub fn remove_with(&mut self, item: JsValue, eq: &Function) -> Result<(), JsValue> {
+        if item.is_null() || item.is_undefined() {
+            return Ok(());
+        }
+        let bbox = self.lookup_bbox(&item)?;
+        let equals = |data: &JsValue| eq.call2(&JsValue::NULL, data, &item).map(|same| same.is_truthy());
+        if self.frozen_nodes > 0 && self.frozen_holds(&bbox, |data| equals(data).unwrap_or(false)) {
+            return Err(JsError::new("cannot remove an item from a frozen region").into());
+        }
+
+        let mut error = None;
+        let mut reinsert = Vec::new();
+        let removed = RBushCore::remove_from_node(
+            &mut self.core.root,
+            &mut |data| {
+                error.is_none()
+                    && equals(data).unwrap_or_else(|e| {
+                        error = Some(e);
+                        false
+                    })
+            },
+            &bbox,
+            self.core.min_entries,
+            &mut reinsert,
+        );
+        if let Some(entry) = removed {
+            self.finish_removal(reinsert, &[entry]);
+        }
+        match error {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    // Takes out the `k` items whose boxes overlap their siblings the most
+    // (summed `intersection_area` within their leaf node) and inserts them
+    // again through the normal insert path. Items that overlap nothing are
+    // never picked. Returns how many were reinserted.
+    #[wasm_bindgen(js_name = reinsertWorst)]
+    pub fn reinsert_worst(&mut self, k: usize) -> usize {
+        let mut scored = Vec::new();
+        let mut stack = vec![&self.core.root];
+        while let Some(node) = stack.pop() {
+            if node.frozen {
+                continue;
+            }
+            if node.height > 1 {
+                stack.extend(node.children.iter());
+                continue;
+            }
+            for (i, child) in node.children.iter().enumerate() {
+                let overlap: f64 = node
+                    .children
+                    .iter()
+                    .enumerate()
+                    .filter(|&(j, _)| j != i)
+                    .map(|(_, sibling)| child.bbox.intersection_area(&sibling.bbox))
+                    .sum();
+                if overlap > 0.0 {
+                    scored.push((overlap, child.bbox, child.data().clone()));
+                }
+            }
+        }
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        scored.truncate(k);
+
+        let mut reinsert = Vec::new();
+        let mut taken = Vec::with_capacity(scored.len());
+        for (_, bbox, data) in &scored {
+            if let Some(entry) = RBushCore::remove_from_node(
+                &mut self.core.root,
+                &mut |candidate| candidate == data,
+                bbox,
+                self.core.min_entries,
+                &mut reinsert,
+            ) {
+                taken.push(entry);
+            }
+        }
+        self.finish_removal(reinsert, &[]);
+
+        let count = taken.len();
+        self.core.stats.reinserts += count;
+        for entry in taken {
+            self.core._insert_at_level(entry, 0);
+        }
+        count
+    }
+
+    // Visits every item intersecting the box and removes those for which
+    // `predicate(data)` is falsy (the `Array.prototype.filter` convention),
+    // condensing the tree once at the end. If the predicate throws, items
+    // not yet visited are kept, the tree is left valid, and the error is
+    // rethrown.
+    #[wasm_bindgen(js_name = searchAndFilter)]
+    pub fn search_and_filter(&mut self, bbox_js: &JsValue, predicate: &Function) -> Result<usize, JsValue> {
+        let bbox = self.read_bbox(bbox_js)?;
+        let mut reinsert = Vec::new();
+        let mut removed = Vec::new();
+        let mut error = None;
+
+        if bbox.intersects(&self.core.root.bbox) {
+            RBush::filter_node(
+                &mut self.core.root,
+                &bbox,
+                &mut |leaf| {
+                    predicate
+                        .call1(&JsValue::NULL, leaf.data())
+                        .map(|keep| keep.is_truthy())
+                },
+                self.core.min_entries,
+                &mut reinsert,
+                &mut removed,
+                &mut error,
+            );
+        }
+        let count = removed.len();
+        if count > 0 {
+            self.finish_removal(reinsert, &removed);
+        }
+
+        match error {
+            Some(e) => Err(e),
+            None => Ok(count),
+        }
+    }
+
+    // Removes every item whose box equals the given one, each coordinate
+    // within `epsilon` (default 0, an exact match), condensing the tree
+    // once at the end. Items under frozen nodes are kept. Returns how many
+    // were removed.
+    #[wasm_bindgen(js_name = removeByBox)]
+    pub fn remove_by_box(&mut self, min_x: f64, min_y: f64, max_x: f64, max_y: f64, epsilon: Option<f64>) -> usize {
+        let target = Rect::new(min_x, min_y, max_x, max_y);
+        let epsilon = epsilon.unwrap_or(0.0).max(0.0);
+        let bbox = Rect::new(min_x - epsilon, min_y - epsilon, max_x + epsilon, max_y + epsilon);
+        let close = |a: f64, b: f64| (a - b).abs() <= epsilon;
+        let mut reinsert = Vec::new();
+        let mut removed = Vec::new();
+
+        if bbox.intersects(&self.core.root.bbox) {
+            RBush::filter_node(
+                &mut self.core.root,
+                &bbox,
+                &mut |leaf| {
+                    let e = leaf.shape();
+                    Ok(!(close(e.min_x, target.min_x)
+                        && close(e.min_y, target.min_y)
+                        && close(e.max_x, target.max_x)
+                        && close(e.max_y, target.max_y)))
+                },
+                self.core.min_entries,
+                &mut reinsert,
+                &mut removed,
+                &mut None,
+            );
+        }
+        let count = removed.len();
+        if count > 0 {
+            self.finish_removal(reinsert, &removed);
+        }
+        count
+    }
+
+    // Moves every item fully inside the box from this tree into `other`
+    // (bulk-loaded there) and returns how many moved. Moved items lose
+    // their pin, since pin ids are per tree.
+    #[wasm_bindgen(js_name = transferRegion)]
+    pub fn transfer_region(&mut self, other: &mut RBush, bbox_js: &JsValue) -> Result<usize, JsError> {
+        let bbox = self.read_bbox(bbox_js)?;
+        other.check_insert(&bbox, 0)?;
+        let mut reinsert = Vec::new();
+        let mut moved = Vec::new();
+
+        if bbox.intersects(&self.core.root.bbox) {
+            RBush::filter_node(
+                &mut self.core.root,
+                &bbox,
+                &mut |leaf| Ok(!bbox.contains(leaf.shape())),
+                self.core.min_entries,
+                &mut reinsert,
+                &mut moved,
+                &mut None,
+            );
+        }
+        let count = moved.len();
+        if count == 0 {
+            return Ok(0);
+        }
+        self.finish_removal(reinsert, &moved);
+
+        for entry in &mut moved {
+            entry.pin = 0;
+        }
+        other.core.stats.inserts += count;
+        other.core.load_entries(moved);
+        Ok(count)
+    }
+
+    // Moves the items under `node` (within `bbox`) that `keep` rejects into
+    // `removed`, and hands underflowing child nodes to `reinsert`. Returns
+    // whether anything below changed.
+    #[allow(clippy::too_many_arguments)]
+    fn filter_node(
+        node: &mut Entry,
+        bbox: &Rect,
+        keep: &mut impl FnMut(&Entry) -> Result<bool, JsValue>,
+        min_entries: usize,
+        reinsert: &mut Vec<Entry>,
+        removed: &mut Vec<Entry>,
+        error: &mut Option<JsValue>,
+    ) -> bool {
+        let mut changed = false;
+        let children = std::mem::take(&mut node.children);
+        let mut kept = Vec::with_capacity(children.len());
+
+        for mut child in children {
+            if error.is_some() || child.frozen || !bbox.intersects(child.shape()) {
+                kept.push(child);
+            } else if child.is_leaf {
+                match keep(&child) {
+                    Ok(true) => kept.push(child),
+                    Ok(false) => {
+                        removed.push(child);
+                        changed = true;
+                    }
+                    Err(e) => {
+                        *error = Some(e);
+                        kept.push(child);
+                    }
+                }
+            } else if RBush::filter_node(&mut child, bbox, keep, min_entries, reinsert, removed, error) {
+                changed = true;
+                if child.children.len() < min_entries && !child.holds_frozen() {
+                    RBushCore::collect_items(child, reinsert);
+                } else {
+                    kept.push(child);
+                }
+            } else {
+                kept.push(child);
+            }
+        }
+
+        node.children = kept;
+        if changed {
+            node.calc_bbox();
+        }
+        changed
+    }
+
+    // Condenses the tree after `removed` were taken out, reinserting the
+    // items of underflowed nodes, and drops the pins of the removed items.
+    fn finish_removal(&mut self, reinsert: Vec<Entry>, removed: &[Entry]) {
+        for entry in removed {
+            if entry.pin != 0 {
+                self.pins.remove(&entry.pin);
+            }
+        }
+        let count = removed.len();
+        self.core.condense(reinsert, count);
+
+        self.pending_removals += count;
+        if self.auto_compact_after > 0
+            && self.pending_removals >= self.auto_compact_after
+            && self.frozen_nodes == 0
+        {
+            self.rebuild_tree();
+        }
+    }
+
+    // Rebuilds the tree from its current items with a fresh bulk load,
+    // undoing the drift left by incremental inserts and removals. Refused
+    // while any node is frozen, since the rebuild would reshape it.
+    pub fn optimize(&mut self) -> Result<(), JsError> {
+        if self.frozen_nodes > 0 {
+            return Err(JsError::new("cannot rebuild a tree with frozen nodes"));
+        }
+        self.rebuild_tree();
+        Ok(())
+    }
+
+    // Alias of `optimize`, under the name rbush users look for.
+    pub fn rebuild(&mut self) -> Result<(), JsError> {
+        self.optimize()
+    }
+
+    fn rebuild_tree(&mut self) {
+        let root = std::mem::replace(&mut self.core.root, Entry::new_node(vec![]));
+        let mut items = Vec::new();
+        RBushCore::collect_items(root, &mut items);
+        self.pending_removals = 0;
+        if !items.is_empty() {
+            self.core.load_entries(items);
+        }
+    }
+
+    // Running counters since construction, `clear()` or `resetOpStats()`.
+    // Inserts count items added through insert and load calls; reinserts
+    // count items moved back in by condensing or `reinsertWorst`.
+    // `rootSplits` counts the times the tree grew a level.
+    #[wasm_bindgen(js_name = opStats)]
+    pub fn op_stats(&self) -> JsValue {
+        let obj = Object::new();
+        let _ = Reflect::set(&obj, &"inserts".into(), &(self.core.stats.inserts as f64).into());
+        let _ = Reflect::set(&obj, &"splits".into(), &(self.core.stats.splits as f64).into());
+        let _ = Reflect::set(&obj, &"rootSplits".into(), &(self.core.stats.root_splits as f64).into());
+        let _ = Reflect::set(&obj, &"removals".into(), &(self.core.stats.removals as f64).into());
+        let _ = Reflect::set(&obj, &"reinserts".into(), &(self.core.stats.reinserts as f64).into());
+        obj.into()
+    }
+
+    #[wasm_bindgen(js_name = resetOpStats)]
+    pub fn reset_op_stats(&mut self) {
+        self.core.stats = OpStats::default();
+    }
+
+    // Freezes every node lying entirely inside the box, and with it the
+    // subtree below. Inserts overlapping a frozen node and removals of items
+    // under one throw; loads, `insertLevel` and `transferRegion` into the
+    // tree are checked the same way, and `searchAndFilter`,
+    // `transferRegion` (out of the tree) and `reinsertWorst` leave frozen
+    // items in place. `optimize` throws while anything is frozen. Returns
+    // how many nodes were newly frozen.
+    #[wasm_bindgen(js_name = freezeRegion)]
+    pub fn freeze_region(&mut self, bbox_js: &JsValue) -> Result<usize, JsError> {
+        let bbox = self.read_bbox(bbox_js)?;
+        let mut frozen = 0;
+        let mut stack = vec![&mut self.core.root];
+
+        while let Some(node) = stack.pop() {
+            if node.children.is_empty() || !bbox.intersects(&node.bbox) {
+                continue;
+            }
+            if bbox.contains(&node.bbox) && !node.frozen {
+                node.frozen = true;
+                frozen += 1;
+            }
+            stack.extend(node.children.iter_mut().filter(|child| !child.is_leaf));
+        }
+        self.frozen_nodes += frozen;
+        Ok(frozen)
+    }
+
+    #[wasm_bindgen(js_name = unfreezeAll)]
+    pub fn unfreeze_all(&mut self) {
+        let mut stack = vec![&mut self.core.root];
+        while let Some(node) = stack.pop() {
+            node.frozen = false;
+            stack.extend(node.children.iter_mut().filter(|child| !child.is_leaf));
+        }
+        self.frozen_nodes = 0;
+    }
+
+    // Chooses the point-to-box distance ("euclidean", "manhattan" or
+    // "chebyshev") used by the nearest-neighbor queries.
+    #[wasm_bindgen(js_name = setMetric)]
+    pub fn set_metric(&mut self, name: &str) -> Result<(), JsError> {
+        self.metric = Metric::parse(name)?;
+        Ok(())
+    }
+
+    pub fn metric(&self) -> String {
+        self.metric.name().to_string()
+    }
+
+    // In strict mode every method reading a bbox from a JS object (inserts,
+    // loads, searches, removals, `fromJSON`) throws on a missing or
+    // non-numeric minX/minY/maxX/maxY or on max < min, rather than reading
+    // bad fields as 0.
+    #[wasm_bindgen(js_name = setStrict)]
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    #[wasm_bindgen(js_name = pendingRemovals)]
+    pub fn pending_removals(&self) -> usize {
+        self.pending_removals
+    }
+
+    // Shifts every stored bbox in place; translation preserves every spatial
+    // relationship, so the structure stays valid without a rebuild. The item
+    // objects themselves are not modified.
+    pub fn translate(&mut self, dx: f64, dy: f64) {
+        self.transform(1.0, 1.0, dx, dy);
+    }
+
+    // Uniform scale about `(pivot_x, pivot_y)`, the origin by default.
+    pub fn scale(&mut self, factor: f64, pivot_x: Option<f64>, pivot_y: Option<f64>) {
+        let px = pivot_x.unwrap_or(0.0);
+        let py = pivot_y.unwrap_or(0.0);
+        self.transform(factor, factor, px - px * factor, py - py * factor);
+    }
+
+    #[wasm_bindgen(js_name = scaleXY)]
+    pub fn scale_xy(&mut self, sx: f64, sy: f64) {
+        self.transform(sx, sy, 0.0, 0.0);
+    }
+
+    // Applies `x * sx + dx`, `y * sy + dy` to every stored bbox. Per-axis
+    // affine maps keep every containment relation intact, so no rebuild is
+    // needed; negative scales mirror the boxes.
+    pub fn transform(&mut self, sx: f64, sy: f64, dx: f64, dy: f64) {
+        if !self.core.root.children.is_empty() {
+            self.core.root.transform(sx, sy, dx, dy);
+        }
+        for bbox in self.pins.values_mut() {
+            bbox.transform(sx, sy, dx, dy);
+        }
+    }
+
+    #[wasm_bindgen(js_name = insertLevel)]
+    pub fn insert_level(&mut self, nodes_json: &JsValue, level: usize) -> Result<(), JsError> {
+        let js_nodes = nodes_json
+            .dyn_ref::<Array>()
+            .ok_or_else(|| JsError::new("insertLevel expects an array of nodes"))?;
+        let mut nodes = Vec::with_capacity(js_nodes.length() as usize);
+        for i in 0..js_nodes.length() {
+            let node = Entry::from_js_object(&js_nodes.get(i), &|item| self.read_bbox(item), None)?;
+            if !node.children.is_empty() {
+                nodes.push(node);
+            }
+        }
+        if nodes.is_empty() {
+            return Ok(());
+        }
+
+        if self.core.root.children.is_empty() {
+            if level != 1 {
+                return Err(JsError::new("an empty tree only accepts nodes at level 1"));
+            }
+            let height = nodes[0].height;
+            if nodes.iter().any(|node| node.height != height) {
+                return Err(JsError::new("all nodes must share the same height"));
+            }
+            let mut root = Entry::new_node(vec![]);
+            root.height = height + 1;
+            self.core.root = root;
+        } else if level == 0 || level >= self.core.root.height {
+            return Err(JsError::new(&format!(
+                "level must be between 1 and {}",
+                self.core.root.height - 1
+            )));
+        }
+
+        let height = self.core.root.height - level;
+        if let Some(node) = nodes.iter().find(|node| node.height != height) {
+            return Err(JsError::new(&format!(
+                "level {} expects nodes of height {}, got {}",
+                level, height, node.height
+            )));
+        }
+
+        for node in &nodes {
+            self.check_insert(&node.bbox, height)?;
+        }
+        self.core.graft_entries(nodes, height);
+        Ok(())
+    }
+
+    // Item entries for `coords[4 * i..4 * i + 4]` and `items[i]`, skipping
+    // null and undefined items.
+    fn hybrid_entries(coords: &[f64], items: &Array) -> Vec<Entry> {
+        let count = coords.len() / 4;
+        let mut entries = Vec::with_capacity(count);
+
+        for i in 0..count {
+            let item_data = items.get(i as u32);
+
+            if item_data.is_null() || item_data.is_undefined() {
+                continue;
+            }
+
+            let start = i * 4;
+            let bbox = Rect::from_flat(&coords[start..start + 4]);
+
+            entries.push(Entry::leaf_with_bbox(bbox, item_data));
+        }
+        entries
+    }
+
+    fn read_bbox(&self, item: &JsValue) -> Result<Rect, JsError> {
+        if self.strict {
+            Rect::from_js_strict(item)
+        } else {
+            Ok(Rect::from_js(item))
+        }
+    }
+
+    // `Rect::lookup_js`, except that in strict mode an object item must carry
+    // a well-formed bbox.
+    fn lookup_bbox(&self, item: &JsValue) -> Result<Rect, JsError> {
+        if self.strict && item.is_object() {
+            Rect::from_js_strict(item)
+        } else {
+            Ok(Rect::lookup_js(item))
+        }
+    }
+
+    fn insert_entry(&mut self, item: Entry) -> Result<(), JsError> {
+        self.check_insert(&item.bbox, 0)?;
+        self.core.stats.inserts += 1;
+        self.core._insert_at_level(item, 0);
+        Ok(())
+    }
+
+    // Fails when inserting an entry of height `level` with this bbox would
+    // change a frozen node: the bbox overlaps one, or the insertion path runs
+    // through one because every candidate child was frozen.
+    fn check_insert(&self, bbox: &Rect, level: usize) -> Result<(), JsError> {
+        if self.frozen_nodes == 0 {
+            return Ok(());
+        }
+        let mut blocked = self.overlaps_frozen(bbox);
+        let mut node = &self.core.root;
+        while !blocked && node.height > level + 1 {
+            node = &node.children[RBushCore::choose_subtree(node, bbox)];
+            blocked = node.frozen;
+        }
+        if blocked {
+            return Err(JsError::new("cannot insert into a frozen region"));
+        }
+        Ok(())
+    }
+
+    fn check_load(&self, items: &[Entry]) -> Result<(), JsError> {
+        if self.frozen_nodes > 0 {
+            for item in items {
+                self.check_insert(&item.bbox, 0)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Whether a frozen node intersects the box. A frozen root counts even
+    // when it doesn't, since any insert would have to grow it.
+    fn overlaps_frozen(&self, bbox: &Rect) -> bool {
+        let mut stack = vec![&self.core.root];
+        while let Some(node) = stack.pop() {
+            if node.frozen {
+                return true;
+            }
+            stack.extend(
+                node.children
+                    .iter()
+                    .filter(|child| !child.is_leaf && bbox.intersects(child.shape())),
+            );
+        }
+        false
+    }
+
+    // Whether an item whose data satisfies `is_item` sits below a frozen
+    // node. Only nodes containing `bbox` are searched.
+    fn frozen_holds(&self, bbox: &Rect, is_item: impl Fn(&JsValue) -> bool) -> bool {
+        let mut stack = vec![(&self.core.root, self.core.root.frozen)];
+        while let Some((node, frozen)) = stack.pop() {
+            for child in &node.children {
+                if !child.bbox.contains(bbox) {
+                    continue;
+                }
+                if !child.is_leaf {
+                    stack.push((child, frozen || child.frozen));
+                } else if frozen && is_item(child.data()) {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    // Whether any item's bbox contains the point, edges included, stopping
+    // at the first hit.
+    fn point_covered(&self, x: f64, y: f64) -> bool {
+        let mut stack = vec![&self.core.root];
+
+        while let Some(node) = stack.pop() {
+            if !node.bbox.contains_point(x, y) {
+                continue;
+            }
+
+            for child in &node.children {
+                if child.shape().contains_point(x, y) {
+                    if child.is_leaf {
+                        return true;
+                    }
+                    stack.push(child);
+                }
+            }
+        }
+        false
+    }
+
+    // Depth-first search with a custom hit test, which must hold for a node
+    // whenever it holds for anything inside it.
+    fn search_by(&self, hit: impl Fn(&Rect) -> bool) -> Array {
+        let result = Array::new();
+        let mut stack = vec![&self.core.root];
+
+        while let Some(node) = stack.pop() {
+            if !hit(&node.bbox) {
+                continue;
+            }
+
+            for child in &node.children {
+                if hit(child.shape()) {
+                    if child.is_leaf {
+                        result.push(child.data());
+                    } else {
+                        stack.push(child);
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    fn matching_leaves(&self, bbox: &Rect) -> Vec<&Entry> {
+        let mut matches = Vec::new();
+        let mut stack = vec![&self.core.root];
+
+        while let Some(node) = stack.pop() {
+            if !bbox.intersects(&node.bbox) {
+                continue;
+            }
+
+            for child in &node.children {
+                if bbox.intersects(child.shape()) {
+                    if child.is_leaf {
+                        matches.push(child);
+                    } else {
+                        stack.push(child);
+                    }
+                }
+            }
+        }
+        matches
+    }
+
+    // Best-first k-nearest search by squared point-to-bbox distance. Nodes are
+    // expanded lazily, so only the part of the tree closer than the k-th
+    // accepted leaf is visited.
+    fn nearest_leaves(
+        &self,
+        x: f64,
+        y: f64,
+        k: usize,
+        accept: impl FnMut(&Entry) -> bool,
+    ) -> Vec<(f64, &Entry)> {
+        let metric = self.metric;
+        self.nearest_leaves_by(|bbox| metric.rank(bbox, x, y), k, f64::INFINITY, accept)
+    }
+
+    // `dist` must never overestimate: a node's distance has to be <= the
+    // distance of everything below it for the early exit to be exact.
+    // Entries with `dist` above `max_dist` are never queued.
+    fn nearest_leaves_by(
+        &self,
+        dist: impl Fn(&Rect) -> f64,
+        k: usize,
+        max_dist: f64,
+        mut accept: impl FnMut(&Entry) -> bool,
+    ) -> Vec<(f64, &Entry)> {
+        let mut result = Vec::new();
+        let root_dist = dist(&self.core.root.bbox);
+        if k == 0 || root_dist > max_dist {
+            return result;
+        }
+
+        let mut queue = BinaryHeap::new();
+        queue.push(Candidate {
+            dist: root_dist,
+            entry: &self.core.root,
+        });
+
+        while let Some(Candidate { dist: entry_dist, entry }) = queue.pop() {
+            if entry.is_leaf {
+                if accept(entry) {
+                    result.push((entry_dist, entry));
+                    if result.len() == k {
+                        break;
+                    }
+                }
+                continue;
+            }
+
+            for child in &entry.children {
+                let child_dist = dist(child.shape());
+                if child_dist <= max_dist {
+                    queue.push(Candidate {
+                        dist: child_dist,
+                        entry: child,
+                    });
+                }
+            }
+        }
+        result
+    }
+
+    fn _all(&self, node: &Entry, result: &Array) {
+        let mut stack = vec![node];
+        while let Some(n) = stack.pop() {
+            for child in &n.children {
+                if child.is_leaf {
+                    result.push(child.data());
+                } else {
+                    stack.push(child);
+                }
+            }
+        }
+    }
+
+    // Stores a copy of the current tree and returns its version id. The copy
+    // duplicates the node structure (O(n)) but shares the item objects.
+    pub fn snapshot(&mut self) -> u64 {
+        let version = self.next_snapshot;
+        self.next_snapshot += 1;
+        self.snapshots.insert(version, self.core.root.clone());
+        version
+    }
+
+    #[wasm_bindgen(js_name = searchVersion)]
+    pub fn search_version(&self, version: u64, bbox_js: &JsValue) -> Result<Array, JsError> {
+        let root = self
+            .snapshots
+            .get(&version)
+            .ok_or_else(|| JsError::new(&format!("unknown snapshot version {}", version)))?;
+        Ok(self.search_in(root, &self.read_bbox(bbox_js)?))
+    }
+
+    #[wasm_bindgen(js_name = dropSnapshot)]
+    pub fn drop_snapshot(&mut self, version: u64) -> bool {
+        self.snapshots.remove(&version).is_some()
+    }
+
+    // Copy of the node hierarchy and bboxes with every item's data set to
+    // null, so no payload is cloned. Queries work as on the original but
+    // return null in place of each item: `search` on the copy gives one
+    // null per match. Snapshots, pins and op stats are not copied.
+    #[wasm_bindgen(js_name = cloneStructure)]
+    pub fn clone_structure(&self) -> RBush {
+        let mut tree = RBush::new(Some(self.core.max_entries), Some(self.auto_compact_after), None);
+        tree.core.min_entries = self.core.min_entries;
+        tree.metric = self.metric;
+        tree.core.root = self.core.root.clone_structure();
+        tree.frozen_nodes = self.frozen_nodes;
+        tree.strict = self.strict;
+        tree
+    }
+
+    // Independent copy of the tree for speculative edits. Nodes are
+    // duplicated (O(n)) while item data is shared by reference, so changes
+    // to either tree never show up in the other's queries. Pins and
+    // buffered `pushLoadChunk` items carry over; snapshots and op stats
+    // do not. Named `clone` because `snapshot` already saves a version
+    // inside this tree and returns its id.
+    #[wasm_bindgen(js_name = clone)]
+    pub fn clone_tree(&self) -> RBush {
+        let mut tree = RBush::new(Some(self.core.max_entries), Some(self.auto_compact_after), None);
+        tree.core.min_entries = self.core.min_entries;
+        tree.core.root = self.core.root.clone();
+        tree.pending_removals = self.pending_removals;
+        tree.pins = self.pins.clone();
+        tree.next_pin = self.next_pin;
+        tree.metric = self.metric;
+        tree.pending_load = self.pending_load.clone();
+        tree.frozen_nodes = self.frozen_nodes;
+        tree.strict = self.strict;
+        tree
+    }
+
+    // Child order is part of the structure: the same nodes in a different
+    // order compare unequal.
+    #[wasm_bindgen(js_name = structurallyEquals)]
+    pub fn structurally_equals(&self, other: &RBush) -> bool {
+        self.core.root.structurally_equals(&other.core.root)
+    }
+
+    // Every bottom-level node (the ones holding items directly), with its
+    // bbox and items. A tree that is a single node returns the root.
+    #[wasm_bindgen(js_name = leafClusters)]
+    pub fn leaf_clusters(&self) -> Array {
+        let result = Array::new();
+        let mut stack = vec![&self.core.root];
+
+        while let Some(node) = stack.pop() {
+            if node.height > 1 {
+                stack.extend(node.children.iter().filter(|child| !child.is_leaf));
+                continue;
+            }
+            if node.children.is_empty() {
+                continue;
+            }
+
+            let items = Array::new();
+            for child in &node.children {
+                items.push(child.data());
+            }
+            let obj = node.bbox.to_js();
+            let _ = Reflect::set(&obj, &"items".into(), &items.into());
+            result.push(&obj.into());
+        }
+        result
+    }
+
+    // Every unordered pair of items whose bboxes intersect, as [dataA,
+    // dataB] with A before B in traversal order. Each item's box is searched
+    // against the tree and only partners later in that order are kept, so
+    // a pair is reported once and no item is paired with itself.
+    #[wasm_bindgen(js_name = selfIntersections)]
+    pub fn self_intersections(&self) -> Array {
+        let leaves = self.matching_leaves(&self.core.root.bbox);
+        let order: HashMap<*const Entry, usize> = leaves
+            .iter()
+            .enumerate()
+            .map(|(i, &leaf)| (leaf as *const Entry, i))
+            .collect();
+
+        let result = Array::new();
+        for (i, leaf) in leaves.iter().enumerate() {
+            for other in self.matching_leaves(leaf.shape()) {
+                if order[&(other as *const Entry)] > i {
+                    result.push(&Array::of2(leaf.data(), other.data()).into());
+                }
+            }
+        }
+        result
+    }
+
+    // Number of intersecting pairs of sibling nodes, summed over the tree.
+    // Items are not compared, only the nodes the tree built around them.
+    #[wasm_bindgen(js_name = fragmentationScore)]
+    pub fn fragmentation_score(&self) -> usize {
+        let mut score = 0;
+        let mut stack = vec![&self.core.root];
+
+        while let Some(node) = stack.pop() {
+            if node.height <= 1 {
+                continue;
+            }
+            for (i, a) in node.children.iter().enumerate() {
+                score += node.children[i + 1..]
+                    .iter()
+                    .filter(|b| a.bbox.intersects(&b.bbox))
+                    .count();
+            }
+            stack.extend(node.children.iter());
+        }
+        score
+    }
+
+    // Leaf clusters in which more than `max_overlap` items overlap at least
+    // one other item of the same cluster, as {minX, minY, maxX, maxY, count}.
+    // Overlaps are only counted within a cluster, so piles that straddle
+    // several leaf nodes are not merged into one hotspot.
+    #[wasm_bindgen(js_name = findHotspots)]
+    pub fn find_hotspots(&self, max_overlap: usize) -> Array {
+        let result = Array::new();
+        let mut stack = vec![&self.core.root];
+
+        while let Some(node) = stack.pop() {
+            if node.height > 1 {
+                stack.extend(node.children.iter().filter(|child| !child.is_leaf));
+                continue;
+            }
+
+            let mut overlapping = vec![false; node.children.len()];
+            for i in 0..node.children.len() {
+                for j in i + 1..node.children.len() {
+                    if node.children[i].bbox.intersects(&node.children[j].bbox) {
+                        overlapping[i] = true;
+                        overlapping[j] = true;
+                    }
+                }
+            }
+
+            let count = overlapping.iter().filter(|&&o| o).count();
+            if count > max_overlap {
+                let obj = node.bbox.to_js();
+                let _ = Reflect::set(&obj, &"count".into(), &(count as f64).into());
+                result.push(&obj.into());
+            }
+        }
+        result
+    }
+
+    // One {minX, minY, maxX, maxY, count} per direct child of the root, read
+    // from the per-node item counts. When the root holds items directly,
+    // each item is its own child with a count of 1.
+    #[wasm_bindgen(js_name = rootChildrenSummary)]
+    pub fn root_children_summary(&self) -> Array {
+        let result = Array::new();
+        for child in &self.core.root.children {
+            let obj = child.bbox.to_js();
+            let _ = Reflect::set(&obj, &"count".into(), &(child.count as f64).into());
+            result.push(&obj.into());
+        }
+        result
+    }
+
+    // The smallest-area node whose bbox contains the whole query box, as
+    // {minX, minY, maxX, maxY, count}, or null if not even the root does.
+    // Overlapping siblings can both contain the box, so every containing
+    // branch is checked rather than a single root-to-leaf path.
+    #[wasm_bindgen(js_name = tightestContainingNode)]
+    pub fn tightest_containing_node(&self, bbox_js: &JsValue) -> Result<JsValue, JsError> {
+        let bbox = self.read_bbox(bbox_js)?;
+        let mut best: Option<&Entry> = None;
+        let mut stack = vec![&self.core.root];
+
+        while let Some(node) = stack.pop() {
+            if node.is_leaf || !node.bbox.contains(&bbox) {
+                continue;
+            }
+            if best.is_none_or(|b| node.bbox.area() < b.bbox.area()) {
+                best = Some(node);
+            }
+            stack.extend(node.children.iter());
+        }
+
+        match best {
+            Some(node) => {
+                let obj = node.bbox.to_js();
+                let _ = Reflect::set(&obj, &"count".into(), &(node.count as f64).into());
+                Ok(obj.into())
+            }
+            None => Ok(JsValue::NULL),
+        }
+    }
+
+    // Sorted distinct `minX` (axis 0) or `minY` (axis 1) values over all
+    // items, or the max side with `max`. A value is dropped when it lies
+    // within `tolerance` (default 0, exact equality) of the last kept one, so
+    // each run of close values collapses to its smallest. NaN is skipped.
+    #[wasm_bindgen(js_name = distinctCoords)]
+    pub fn distinct_coords(&self, axis: u8, max: Option<bool>, tolerance: Option<f64>) -> Result<Vec<f64>, JsError> {
+        if axis > 1 {
+            return Err(JsError::new(&format!("axis must be 0 (x) or 1 (y), got {}", axis)));
+        }
+        let max = max.unwrap_or(false);
+        let pick = |r: &Rect| match (axis == 0, max) {
+            (true, false) => r.min_x,
+            (false, false) => r.min_y,
+            (true, true) => r.max_x,
+            (false, true) => r.max_y,
+        };
+        let tolerance = tolerance.unwrap_or(0.0).max(0.0);
+
+        let mut values = Vec::with_capacity(self.core.root.count);
+        let mut stack = vec![&self.core.root];
+        while let Some(node) = stack.pop() {
+            for child in &node.children {
+                if child.is_leaf {
+                    let value = pick(child.shape());
+                    if !value.is_nan() {
+                        values.push(value);
+                    }
+                } else {
+                    stack.push(child);
+                }
+            }
+        }
+        values.sort_by(f64::total_cmp);
+
+        let mut distinct: Vec<f64> = Vec::with_capacity(values.len());
+        for value in values {
+            if distinct.last().is_none_or(|&last| value - last > tolerance) {
+                distinct.push(value);
+            }
+        }
+        Ok(distinct)
+    }
+
+    // `{ nodesPerLevel, itemsPerLevel }` as Uint32Arrays indexed by depth,
+    // top-down: index 0 is the root, which counts as a level (so
+    // `nodesPerLevel[0]` is 1), and the last index is the nodes holding the
+    // items. Items all sit at that one depth, so `itemsPerLevel` is 0 above
+    // it. Both arrays are empty for an empty tree.
+    #[wasm_bindgen(js_name = levelSizes)]
+    pub fn level_sizes(&self) -> JsValue {
+        let mut nodes_per_level: Vec<u32> = Vec::new();
+        let mut items_per_level: Vec<u32> = Vec::new();
+
+        if !self.core.root.children.is_empty() {
+            let mut queue = std::collections::VecDeque::from([(&self.core.root, 0)]);
+            while let Some((node, level)) = queue.pop_front() {
+                if nodes_per_level.len() <= level {
+                    nodes_per_level.push(0);
+                    items_per_level.push(0);
+                }
+                nodes_per_level[level] += 1;
+
+                for child in &node.children {
+                    if child.is_leaf {
+                        items_per_level[level] += 1;
+                    } else {
+                        queue.push_back((child, level + 1));
+                    }
+                }
+            }
+        }
+
+        let obj = Object::new();
+        let _ = Reflect::set(
+            &obj,
+            &"nodesPerLevel".into(),
+            &Uint32Array::from(nodes_per_level.as_slice()).into(),
+        );
+        let _ = Reflect::set(
+            &obj,
+            &"itemsPerLevel".into(),
+            &Uint32Array::from(items_per_level.as_slice()).into(),
+        );
+        obj.into()
+    }
+
+    // Flat `[minX, minY, maxX, maxY, ...]` of every node at depth `level`
+    // (0 = root), left to right. Empty for an empty tree or a level at or
+    // beyond the height.
+    #[wasm_bindgen(js_name = levelBoxes)]
+    pub fn level_boxes(&self, level: usize) -> Vec<f64> {
+        let mut boxes = Vec::new();
+        if self.core.root.children.is_empty() || level >= self.core.root.height {
+            return boxes;
+        }
+        let mut nodes = vec![&self.core.root];
+        for _ in 0..level {
+            nodes = nodes.iter().flat_map(|node| &node.children).filter(|child| !child.is_leaf).collect();
+        }
+        for node in nodes {
+            boxes.extend_from_slice(&[node.bbox.min_x, node.bbox.min_y, node.bbox.max_x, node.bbox.max_y]);
+        }
+        boxes
+    }
+
+    // Calls `cb(minX, minY, maxX, maxY, height, isLeaf, childCount)` for
+    // every node, depth first from the root with children in stored order;
+    // `isLeaf` marks nodes holding items (height 1). Returning `false`
+    // (exactly) skips the node's subtree. A throw stops the walk and is
+    // rethrown.
+    #[wasm_bindgen(js_name = forEachNode)]
+    pub fn for_each_node(&self, cb: &Function) -> Result<(), JsValue> {
+        let mut stack = vec![&self.core.root];
+        while let Some(node) = stack.pop() {
+            let args = Array::of5(
+                &node.bbox.min_x.into(),
+                &node.bbox.min_y.into(),
+                &node.bbox.max_x.into(),
+                &node.bbox.max_y.into(),
+                &(node.height as f64).into(),
+            );
+            args.push(&(node.height == 1).into());
+            args.push(&(node.children.len() as f64).into());
+            if cb.apply(&JsValue::NULL, &args)?.as_bool() == Some(false) {
+                continue;
+            }
+            stack.extend(node.children.iter().rev().filter(|child| !child.is_leaf));
+        }
+        Ok(())
+    }
+
+    // The node hierarchy without items: each node is `{ minX, minY, maxX,
+    // maxY, height, childCount, children }`, starting at the root.
+    // `childCount` counts all children, items included, while `children`
+    // holds only child nodes, so nodes of height 1 have an empty `children`
+    // with `childCount` items. An empty tree is its root alone, with an
+    // empty (infinite) box and `childCount` 0.
+    #[wasm_bindgen(js_name = toSkeleton)]
+    pub fn to_skeleton(&self) -> JsValue {
+        self.core.root.to_skeleton_object()
+    }
+
+    #[wasm_bindgen(js_name = toJSON)]
+    pub fn to_json(&self) -> JsValue {
+        self.core.root.to_js_object()
+    }
+
+    #[wasm_bindgen(js_name = fromJSON)]
+    pub fn from_json(&mut self, data: JsValue) -> Result<(), JsError> {
+        if !data.is_null() && !data.is_undefined() {
+            self.core.root = Entry::from_js_object(&data, &|item| self.read_bbox(item), None)?;
+            self.pins.clear();
+            self.frozen_nodes = 0;
+        }
+        Ok(())
+    }
+
+    // Binary counterpart of `toJSON`, all little-endian: the header (see
+    // BYTES_MAGIC), then the nodes in preorder, each as u32 height and u32
+    // child count. Under a height-1 node come its items, as minX, minY,
+    // maxX, maxY and the data, all f64. Only numeric data survives; anything
+    // else is written as NaN and must come back through `fromBytes`'s
+    // `items`. Node bboxes are recomputed on load, and padding, pins, tags
+    // and times are not kept.
+    #[wasm_bindgen(js_name = toBytes)]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(BYTES_HEADER_LEN + self.core.len() * 40);
+        bytes.extend_from_slice(BYTES_MAGIC);
+        bytes.extend_from_slice(&BYTES_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&(self.core.len() as u32).to_le_bytes());
+
+        let mut stack = vec![&self.core.root];
+        while let Some(node) = stack.pop() {
+            bytes.extend_from_slice(&(node.height as u32).to_le_bytes());
+            bytes.extend_from_slice(&(node.children.len() as u32).to_le_bytes());
+            if node.height > 1 {
+                stack.extend(node.children.iter().rev());
+                continue;
+            }
+            for item in &node.children {
+                let e = &item.extent;
+                let data = item.data().as_f64().unwrap_or(f64::NAN);
+                for value in [e.min_x, e.min_y, e.max_x, e.max_y, data] {
+                    bytes.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+        }
+        bytes
+    }
+
+    // Replaces the tree with one written by `toBytes`. `items`, if given,
+    // supplies the data of every item in `allOrdered()` order (the order
+    // they were written in), for trees holding non-numeric data; otherwise
+    // each item's data is the number stored with it. Throws on a bad magic,
+    // an unknown version or a truncated or inconsistent buffer, leaving the
+    // tree unchanged.
+    #[wasm_bindgen(js_name = fromBytes)]
+    pub fn from_bytes(&mut self, bytes: &[u8], items: Option<Array>) -> Result<(), JsError> {
+        if bytes.len() < BYTES_HEADER_LEN || &bytes[..4] != BYTES_MAGIC {
+            return Err(JsError::new("not an rbush-rs binary tree"));
+        }
+        let mut reader = ByteReader { bytes, pos: 4 };
+        let version = reader.u32()?;
+        if version != BYTES_VERSION {
+            return Err(JsError::new(&format!(
+                "unsupported binary format version {version}, expected {BYTES_VERSION}"
+            )));
+        }
+        let count = reader.u32()? as usize;
+        if let Some(items) = &items {
+            if items.length() as usize != count {
+                return Err(JsError::new(&format!("expected {count} items, got {}", items.length())));
+            }
+        }
+
+        let mut next_item = 0;
+        let root = RBush::read_node(&mut reader, items.as_ref(), &mut next_item, None)?;
+        if next_item != count || reader.pos != bytes.len() {
+            return Err(JsError::new("binary tree doesn't match its header"));
+        }
+        self.core.root = root;
+        self.pins.clear();
+        self.frozen_nodes = 0;
+        Ok(())
+    }
+
+    // Decodes one `to_bytes` node and everything under it. `height` is the
+    // height the parent expects (None for the root).
+    fn read_node(
+        reader: &mut ByteReader,
+        items: Option<&Array>,
+        next_item: &mut usize,
+        height: Option<usize>,
+    ) -> Result<Entry, JsError> {
+        let node_height = reader.u32()? as usize;
+        let child_count = reader.u32()? as usize;
+        if node_height == 0 || node_height > MAX_TREE_HEIGHT || height.is_some_and(|h| h != node_height) {
+            return Err(JsError::new("binary tree has inconsistent node heights"));
+        }
+
+        let mut children = Vec::with_capacity(child_count.min(reader.remaining() / 8));
+        for _ in 0..child_count {
+            if node_height > 1 {
+                children.push(RBush::read_node(reader, items, next_item, Some(node_height - 1))?);
+                continue;
+            }
+            let bbox = Rect::new(reader.f64()?, reader.f64()?, reader.f64()?, reader.f64()?);
+            let value = reader.f64()?;
+            let data = match items {
+                Some(items) => items.get(*next_item as u32),
+                None => value.into(),
+            };
+            *next_item += 1;
+            children.push(Entry::leaf_with_bbox(bbox, data));
+        }
+
+        let mut node = Entry::new_node(children);
+        node.height = node_height;
+        Ok(node)
+    }
+}
+
+// Bounds-checked little-endian cursor over a `to_bytes` buffer.
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl ByteReader<'_> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], JsError> {
+        let chunk = self
+            .bytes
+            .get(self.pos..self.pos + N)
+            .ok_or_else(|| JsError::new("binary tree is truncated"))?;
+        self.pos += N;
+        Ok(chunk.try_into().unwrap())
+    }
+
+    fn u32(&mut self) -> Result<u32, JsError> {
+        Ok(u32::from_le_bytes(self.take()?))
+    }
+
+    fn f64(&mut self) -> Result<f64, JsError> {
+        Ok(f64::from_le_bytes(self.take()?))
+    }
+
+    fn remaining(&self) -> usize {
+        self.bytes.len() - self.pos
+    }
+}
+
+// Paginated `search`, created by `RBush::search_cursor`. The cursor keeps
+// its own traversal stack, as child-index paths from the root, and is
+// handed the tree on each `next_batch`: an exported struct can't borrow
+// another exported object across calls, and owning a copy of the tree
+// would cost the O(n) the cursor exists to avoid.
+#[wasm_bindgen]
+pub struct SearchCursor {
+    // `RBush::id` of the tree the cursor walks.
+    tree: u64,
+    bbox: Rect,
+    // Nodes still to visit, as child-index paths from the root.
+    stack: Vec<Vec<usize>>,
+    // Matches found in the last visited node and not yet returned.
+    pending: Vec<JsValue>,
+}
+
+#[wasm_bindgen]
+impl SearchCursor {
+    // Up to `max` further matches, resuming where the previous batch
+    // stopped; an empty array once the search is exhausted. `tree` must be
+    // the tree the cursor came from, and any other tree (a `clone` of it
+    // included) is rejected. If the tree was modified in between, the
+    // remaining results are unspecified (but never out of bounds).
+    #[wasm_bindgen(js_name = nextBatch)]
+    pub fn next_batch(&mut self, tree: &RBush, max: usize) -> Result<Array, JsError> {
+        if tree.id != self.tree {
+            return Err(JsError::new("the cursor was created by another tree"));
+        }
+        let result = Array::new();
+        let mut count = 0;
+
+        while count < max {
+            if let Some(data) = self.pending.pop() {
+                result.push(&data);
+                count += 1;
+                continue;
+            }
+            let Some(path) = self.stack.pop() else {
+                break;
+            };
+            let Some(node) = path
+                .iter()
+                .try_fold(&tree.core.root, |node, &i| node.children.get(i))
+            else {
+                continue;
+            };
+
+            for (i, child) in node.children.iter().enumerate().rev() {
+                if !self.bbox.intersects(child.shape()) {
+                    continue;
+                }
+                if !child.is_leaf {
+                    let mut child_path = path.clone();
+                    child_path.push(i);
+                    self.stack.push(child_path);
+                } else {
+                    self.pending.push(child.data().clone());
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    // Whether the traversal has finished. It can still be false when no
+    // matches remain; the next batch then comes back empty and sets it.
+    pub fn done(&self) -> bool {
+        self.pending.is_empty() && self.stack.is_empty()
+    }
+}